@@ -2,12 +2,17 @@
 extern crate criterion;
 
 use criterion::Criterion;
-use halo2_franchise::halo2::pasta::EqAffine;
-use halo2_franchise::halo2::plonk::*;
-use halo2_franchise::halo2::poly::commitment::Params;
-use halo2_franchise::halo2::transcript::{Blake2bRead, Blake2bWrite, Challenge255};
-use halo2_franchise::{franchise::FranchiseCircuit, utils::generate_test_data};
+use halo2_franchise::halo2::pasta::Fp;
+use halo2_franchise::merkle::MerkleTreeBuilder;
+use halo2_franchise::utils::poseidon_hash2;
+#[cfg(not(feature = "verify-only"))]
+use halo2_franchise::{
+    franchise::FranchiseCircuit,
+    halo2::{pasta::EqAffine, plonk::*, poly::commitment::Params},
+    utils::generate_test_data,
+};
 
+#[cfg(not(feature = "verify-only"))]
 fn bench<const LVL: usize>(k: u32, c: &mut Criterion) {
     let params: Params<EqAffine> = Params::new(k);
     let empty_circuit = FranchiseCircuit::<LVL>::default();
@@ -17,45 +22,114 @@ fn bench<const LVL: usize>(k: u32, c: &mut Criterion) {
     let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
 
     let (circuit, public) = generate_test_data::<LVL>();
+    let public = public.to_vec();
 
     let prover_name = format!("franchise-prove-k{}-lvl{}", k, LVL);
     let verifier_name = format!("franchise-verify-k{}-lvl{}", k, LVL);
 
     c.bench_function(&prover_name, |b| {
         b.iter(|| {
-            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-            create_proof(
-                &params,
-                &pk,
-                &[circuit.clone()],
-                &[&[&public]],
-                &mut transcript,
-            )
-            .expect("proof generation should not fail");
-            transcript.finalize();
+            circuit
+                .prove(&params, &pk, &public)
+                .expect("proof generation should not fail");
         })
     });
 
-    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-    create_proof(&params, &pk, &[circuit], &[&[&public]], &mut transcript)
+    let proof = circuit
+        .prove(&params, &pk, &public)
         .expect("proof generation should not fail");
-    let proof = transcript.finalize();
 
     c.bench_function(&verifier_name, |b| {
         b.iter(|| {
-            let msm = params.empty_msm();
-            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-            let guard =
-                verify_proof(&params, pk.get_vk(), msm, &[&[&public]], &mut transcript).unwrap();
-            let msm = guard.clone().use_challenges();
-            assert!(msm.eval());
+            assert_eq!(
+                FranchiseCircuit::<LVL>::verify(&params, pk.get_vk(), &public, &proof),
+                Ok(true)
+            );
         })
     });
 }
 
+fn bench_poseidon(c: &mut Criterion) {
+    // fixed inputs, so runs are comparable
+    let (a, b) = (Fp::from(1), Fp::from(2));
+
+    c.bench_function("poseidon-hash2", |bench| {
+        bench.iter(|| poseidon_hash2(criterion::black_box(a), criterion::black_box(b)))
+    });
+}
+
+fn bench_merkle_build(depth: u32, c: &mut Criterion) {
+    let leaves: Vec<Fp> = (0..2u64.pow(depth - 1)).map(Fp::from).collect();
+
+    // run once with and once without `--features rayon` to compare
+    let mode = if cfg!(feature = "rayon") {
+        "parallel"
+    } else {
+        "serial"
+    };
+    let name = format!("merkle-build-depth{}-{}", depth, mode);
+
+    let mut group = c.benchmark_group("merkle-build");
+    group.sample_size(10);
+    group.bench_function(&name, |b| {
+        b.iter(|| {
+            let mut builder = MerkleTreeBuilder::new(depth);
+            builder
+                .extend(leaves.iter().copied())
+                .expect("leaves should fit the tree");
+            builder.build()
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn bench_verify_batch<const LVL: usize>(k: u32, n: usize, c: &mut Criterion) {
+    let params: Params<EqAffine> = Params::new(k);
+    let empty_circuit = FranchiseCircuit::<LVL>::default();
+    let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+    let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+    // the same proof n times, verification costs the same as distinct ones
+    let (circuit, public) = generate_test_data::<LVL>();
+    let proof = circuit
+        .prove(&params, &pk, &public.to_vec())
+        .expect("proof generation should not fail");
+    let items = vec![(public, proof); n];
+
+    let mut group = c.benchmark_group("verify-batch");
+    group.sample_size(10);
+    group.bench_function(format!("sequential-{}-lvl{}", n, LVL), |b| {
+        b.iter(|| {
+            for (public, proof) in &items {
+                assert_eq!(
+                    FranchiseCircuit::<LVL>::verify(&params, pk.get_vk(), &public.to_vec(), proof),
+                    Ok(true)
+                );
+            }
+        })
+    });
+    group.bench_function(format!("batched-{}-lvl{}", n, LVL), |b| {
+        b.iter(|| {
+            assert_eq!(
+                FranchiseCircuit::<LVL>::verify_batch(&params, pk.get_vk(), &items),
+                Ok(vec![true; n])
+            );
+        })
+    });
+    group.finish();
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
-    bench::<9>(9, c);
-    bench::<21>(10, c);
+    bench_poseidon(c);
+    bench_merkle_build(16, c);
+    bench_merkle_build(20, c);
+    #[cfg(not(feature = "verify-only"))]
+    {
+        bench::<9>(9, c);
+        bench::<21>(10, c);
+        bench_verify_batch::<9>(9, 100, c);
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,254 @@
+//! Command line entry point to the franchise proofs, for integrators outside
+//! Rust. Field elements are read and written as big-endian hex strings, see
+//! [`fp_from_hex`].
+//!
+//! ```text
+//! franchise prove <input.json> <output.json>
+//! franchise setup <depth> <params> <vk>
+//! franchise verify <params> <vk> <proof.json> <public_inputs.json>
+//! ```
+//!
+//! The input has the voter's `secret_key`, the two-element `process_id`, the
+//! `vote_hash` and the census `witness`, as `[sibling, sibling_is_right]`
+//! pairs in the format of `MerkleTree::witness`. Its length is the depth of the
+//! circuit. The output has the `depth`, the `proof` bytes in hex and the
+//! `public_inputs` proved.
+//!
+//! `setup` writes the params and verifying key of a census depth. `verify`
+//! reads the params and checks the stored key with [`check_vk`], which
+//! regenerates the key and compares it with the stored one, then checks the `depth` and `proof` of a `prove` output
+//! against the `census_root`, `nullifier` and `vote_hash` of the public inputs
+//! file. It prints `valid` or `invalid`, exiting with 0 only for valid proofs.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+
+use halo2_franchise::encoding::fp_from_hex;
+use halo2_franchise::franchise::FranchiseCircuit;
+use halo2_franchise::halo2::{
+    pasta::{EqAffine, Fp},
+    plonk::keygen_vk,
+    poly::commitment::Params,
+};
+use halo2_franchise::keys::{check_vk, read_params, write_params, write_vk};
+use halo2_franchise::proof::Proof;
+use halo2_franchise::public_inputs::PublicInputs;
+use halo2_franchise::utils::recommended_k;
+#[cfg(not(feature = "verify-only"))]
+use halo2_franchise::{
+    encoding::fp_to_hex, franchise::FranchiseCircuitBuilder, halo2::plonk::keygen_pk,
+};
+use serde::{Deserialize, Serialize};
+
+const USAGE: &str = "usage:
+    franchise prove <input.json> <output.json>
+    franchise setup <depth> <params> <vk>
+    franchise verify <params> <vk> <proof.json> <public_inputs.json>";
+
+#[cfg(not(feature = "verify-only"))]
+#[derive(Deserialize)]
+struct ProveInput {
+    secret_key: String,
+    process_id: [String; 2],
+    vote_hash: String,
+    witness: Vec<(String, bool)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PublicInputsJson {
+    census_root: String,
+    nullifier: String,
+    vote_hash: String,
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[derive(Serialize, Deserialize)]
+struct ProveOutput {
+    depth: usize,
+    proof: String,
+    public_inputs: PublicInputsJson,
+}
+
+#[derive(Deserialize)]
+struct ProofJson {
+    depth: usize,
+    proof: String,
+}
+
+fn parse(name: &str, hex: &str) -> Result<Fp, String> {
+    fp_from_hex(hex).map_err(|err| format!("invalid {}: {:?}", name, err))
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn prove<const LVL: usize>(input: &ProveInput) -> Result<ProveOutput, String> {
+    let witness = input
+        .witness
+        .iter()
+        .map(|(sibling, right)| Ok((parse("witness sibling", sibling)?, *right)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let (circuit, _) = FranchiseCircuitBuilder::<LVL>::new()
+        .secret_key(parse("secret_key", &input.secret_key)?)
+        .process_id([
+            parse("process_id", &input.process_id[0])?,
+            parse("process_id", &input.process_id[1])?,
+        ])
+        .vote_hash(parse("vote_hash", &input.vote_hash)?)
+        .witness(&witness)
+        .build()
+        .map_err(|err| format!("invalid input: {:?}", err))?;
+    let public = circuit
+        .public_inputs()
+        .expect("the builder sets all the witnesses");
+
+    let params: Params<EqAffine> = Params::new(recommended_k::<LVL>());
+    let empty_circuit = FranchiseCircuit::<LVL>::default();
+    let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+    let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+    let proof = circuit
+        .prove(&params, &pk, &public.to_vec())
+        .map_err(|err| format!("cannot prove: {:?}", err))?;
+
+    Ok(ProveOutput {
+        depth: LVL,
+        proof: proof.to_string(),
+        public_inputs: PublicInputsJson {
+            census_root: fp_to_hex(public.census_root),
+            nullifier: fp_to_hex(public.nullifier),
+            vote_hash: fp_to_hex(public.vote_hash),
+        },
+    })
+}
+
+fn setup<const LVL: usize>((params_path, vk_path): (&str, &str)) -> Result<(), String> {
+    let params: Params<EqAffine> = Params::new(recommended_k::<LVL>());
+    let vk =
+        keygen_vk(&params, &FranchiseCircuit::<LVL>::default()).expect("keygen_vk should not fail");
+
+    let file = File::create(params_path).map_err(|err| format!("cannot create params: {}", err))?;
+    write_params(&params, BufWriter::new(file))
+        .map_err(|err| format!("cannot write params: {}", err))?;
+    let file = File::create(vk_path).map_err(|err| format!("cannot create vk: {}", err))?;
+    write_vk::<LVL>(&vk, BufWriter::new(file)).map_err(|err| format!("cannot write vk: {}", err))
+}
+
+fn verify<const LVL: usize>(
+    (params_path, vk_path, proof, public): (&str, &str, &str, &PublicInputsJson),
+) -> Result<bool, String> {
+    let file = File::open(params_path).map_err(|err| format!("cannot open params: {}", err))?;
+    let params = read_params(BufReader::new(file), recommended_k::<LVL>())
+        .map_err(|err| format!("invalid params: {}", err))?;
+    let file = File::open(vk_path).map_err(|err| format!("cannot open vk: {}", err))?;
+    let vk = check_vk(
+        BufReader::new(file),
+        &params,
+        &FranchiseCircuit::<LVL>::default(),
+    )
+    .map_err(|err| format!("invalid vk: {:?}", err))?;
+
+    let proof: Proof = proof
+        .parse()
+        .map_err(|err| format!("invalid proof: {:?}", err))?;
+    let public = PublicInputs {
+        census_root: parse("census_root", &public.census_root)?,
+        nullifier: parse("nullifier", &public.nullifier)?,
+        vote_hash: parse("vote_hash", &public.vote_hash)?,
+    };
+    // a malformed proof is as invalid as one not verifying
+    Ok(FranchiseCircuit::<LVL>::verify(&params, &vk, &public.to_vec(), &proof).unwrap_or(false))
+}
+
+/// Calls `$f::<depth>` for the depths supported by the binary, the circuit
+/// depth being a const generic.
+macro_rules! with_depth {
+    ($depth:expr, $f:ident($arg:expr), [$($lvl:literal),*]) => {
+        match $depth {
+            $($lvl => $f::<$lvl>($arg),)*
+            depth => Err(format!("unsupported census depth {}", depth)),
+        }
+    };
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn run_prove(input_path: &str, output_path: &str) -> Result<(), String> {
+    let file = File::open(input_path).map_err(|err| format!("cannot open input: {}", err))?;
+    let input: ProveInput = serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| format!("invalid input: {}", err))?;
+
+    let output = with_depth!(
+        input.witness.len(),
+        prove(&input),
+        [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+            25, 26, 27, 28, 29, 30, 31, 32
+        ]
+    )?;
+
+    let file = File::create(output_path).map_err(|err| format!("cannot create output: {}", err))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &output)
+        .map_err(|err| format!("cannot write output: {}", err))
+}
+
+fn run_setup(depth: &str, params_path: &str, vk_path: &str) -> Result<(), String> {
+    let depth: usize = depth
+        .parse()
+        .map_err(|err| format!("invalid depth: {}", err))?;
+    with_depth!(
+        depth,
+        setup((params_path, vk_path)),
+        [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+            25, 26, 27, 28, 29, 30, 31, 32
+        ]
+    )
+}
+
+fn run_verify(
+    params_path: &str,
+    vk_path: &str,
+    proof_path: &str,
+    public_path: &str,
+) -> Result<bool, String> {
+    let file = File::open(proof_path).map_err(|err| format!("cannot open proof: {}", err))?;
+    let proof: ProofJson = serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| format!("invalid proof: {}", err))?;
+    let file =
+        File::open(public_path).map_err(|err| format!("cannot open public inputs: {}", err))?;
+    let public: PublicInputsJson = serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| format!("invalid public inputs: {}", err))?;
+
+    with_depth!(
+        proof.depth,
+        verify((params_path, vk_path, &proof.proof, &public)),
+        [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+            25, 26, 27, 28, 29, 30, 31, 32
+        ]
+    )
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        #[cfg(not(feature = "verify-only"))]
+        ["prove", input, output] => run_prove(input, output),
+        #[cfg(feature = "verify-only")]
+        ["prove", _, _] => Err("built with verify-only, proofs can't be created".to_string()),
+        ["setup", depth, params, vk] => run_setup(depth, params, vk),
+        ["verify", params, vk, proof, public] => {
+            run_verify(params, vk, proof, public).map(|valid| {
+                println!("{}", if valid { "valid" } else { "invalid" });
+                if !valid {
+                    process::exit(1);
+                }
+            })
+        }
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
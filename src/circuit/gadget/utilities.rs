@@ -7,6 +7,7 @@ use crate::halo2::{
 use ff::PrimeFieldBits;
 use pasta_curves::arithmetic::FieldExt;
 
+pub(crate) mod compact_cond_swap;
 pub(crate) mod cond_swap;
 
 /// A variable representing a field element.
@@ -0,0 +1,285 @@
+use std::marker::PhantomData;
+
+use crate::halo2::{
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use pasta_curves::arithmetic::FieldExt;
+
+use super::cond_swap::CondSwapInstructions;
+use crate::circuit::gadget::utilities::{copy, CellValue, UtilitiesInstructions, Var};
+
+/// A chip implementing a conditional swap with three advice columns instead of
+/// the five used by [`CondSwapChip`], at the cost of one extra row per swap: the
+/// swapped pair is assigned in the `a` and `b` columns of the row following the
+/// input pair.
+///
+/// | a         | b         | swap |
+/// |-----------|-----------|------|
+/// | a         | b         | swap |
+/// | a_swapped | b_swapped |      |
+///
+/// [`CondSwapChip`]: super::cond_swap::CondSwapChip
+#[derive(Clone, Debug)]
+pub struct CompactCondSwapChip<F> {
+    config: CompactCondSwapConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> Chip<F> for CompactCondSwapChip<F> {
+    type Config = CompactCondSwapConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+#[derive(Clone, Debug, Hash)]
+pub struct CompactCondSwapConfig {
+    pub q_swap: Selector,
+    pub a: Column<Advice>,
+    pub b: Column<Advice>,
+    pub swap: Column<Advice>,
+}
+
+impl<F: FieldExt> UtilitiesInstructions<F> for CompactCondSwapChip<F> {
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt> CondSwapInstructions<F> for CompactCondSwapChip<F> {
+    #[allow(clippy::type_complexity)]
+    fn swap(
+        &self,
+        mut layouter: impl Layouter<F>,
+        pair: (Self::Var, Self::Var),
+        swap: Option<bool>,
+    ) -> Result<(Self::Var, Self::Var), Error> {
+        let config = self.config();
+
+        layouter.assign_region(
+            || "swap",
+            |mut region| {
+                // Enable `q_swap` selector
+                config.q_swap.enable(&mut region, 0)?;
+
+                // Copy in `a` and `b` values
+                let a = copy(&mut region, || "copy a", config.a, 0, &pair.0)?;
+                let b = copy(&mut region, || "copy b", config.b, 0, &pair.1)?;
+
+                // Witness `swap` value
+                let swap_val = swap.map(|swap| F::from_u64(swap as u64));
+                region.assign_advice(
+                    || "swap",
+                    config.swap,
+                    0,
+                    || swap_val.ok_or(Error::Synthesis),
+                )?;
+
+                let swapped =
+                    a.value().zip(b.value()).zip(swap).map(
+                        |((a, b), swap)| {
+                            if swap {
+                                (b, a)
+                            } else {
+                                (a, b)
+                            }
+                        },
+                    );
+
+                // Conditionally swap a, in the next row of column `a`
+                let a_swapped = {
+                    let a_swapped = swapped.map(|(a, _)| a);
+                    let a_swapped_cell = region.assign_advice(
+                        || "a_swapped",
+                        config.a,
+                        1,
+                        || a_swapped.ok_or(Error::Synthesis),
+                    )?;
+                    CellValue::new(a_swapped_cell, a_swapped)
+                };
+
+                // Conditionally swap b, in the next row of column `b`
+                let b_swapped = {
+                    let b_swapped = swapped.map(|(_, b)| b);
+                    let b_swapped_cell = region.assign_advice(
+                        || "b_swapped",
+                        config.b,
+                        1,
+                        || b_swapped.ok_or(Error::Synthesis),
+                    )?;
+                    CellValue::new(b_swapped_cell, b_swapped)
+                };
+
+                // Return swapped pair
+                Ok((a_swapped, b_swapped))
+            },
+        )
+    }
+}
+
+impl<F: FieldExt> CompactCondSwapChip<F> {
+    /// Configures this chip for use in a circuit.
+    ///
+    /// # Side-effects
+    ///
+    /// `advices[0]` and `advices[1]` will be equality-enabled.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; 3],
+    ) -> CompactCondSwapConfig {
+        let q_swap = meta.selector();
+
+        meta.enable_equality(advices[0].into());
+        meta.enable_equality(advices[1].into());
+        let config = CompactCondSwapConfig {
+            q_swap,
+            a: advices[0],
+            b: advices[1],
+            swap: advices[2],
+        };
+
+        meta.create_gate("a' = b ⋅ swap + a ⋅ (1-swap)", |meta| {
+            let q_swap = meta.query_selector(q_swap);
+
+            let a = meta.query_advice(config.a, Rotation::cur());
+            let b = meta.query_advice(config.b, Rotation::cur());
+
+            let a_swapped = meta.query_advice(config.a, Rotation::next());
+            let b_swapped = meta.query_advice(config.b, Rotation::next());
+            let swap = meta.query_advice(config.swap, Rotation::cur());
+
+            let one = Expression::Constant(F::one());
+
+            // a_swapped - b ⋅ swap - a ⋅ (1-swap) = 0
+            let a_check =
+                a_swapped - b.clone() * swap.clone() - a.clone() * (one.clone() - swap.clone());
+
+            // b_swapped - a ⋅ swap - b ⋅ (1-swap) = 0
+            let b_check = b_swapped - a * swap.clone() - b * (one.clone() - swap.clone());
+
+            // Check `swap` is boolean.
+            let bool_check = swap.clone() * (one - swap);
+
+            IntoIterator::into_iter([a_check, b_check, bool_check])
+                .map(move |poly| q_swap.clone() * poly)
+        });
+
+        config
+    }
+
+    pub fn construct(config: CompactCondSwapConfig) -> Self {
+        CompactCondSwapChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+    use pasta_curves::{arithmetic::FieldExt, pallas::Base};
+
+    use super::super::cond_swap::{CondSwapChip, CondSwapConfig};
+    use super::*;
+
+    #[test]
+    fn compact_cond_swap() {
+        #[derive(Default)]
+        struct MyCircuit<F: FieldExt> {
+            a: Option<F>,
+            b: Option<F>,
+            swap: Option<bool>,
+        }
+
+        #[derive(Clone)]
+        struct Config {
+            swap: CondSwapConfig,
+            compact_swap: CompactCondSwapConfig,
+        }
+
+        impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
+            type Config = Config;
+            type FloorPlanner = SimpleFloorPlanner;
+
+            fn without_witnesses(&self) -> Self {
+                Self::default()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                // 5 advice columns for the regular chip...
+                let advices = [
+                    meta.advice_column(),
+                    meta.advice_column(),
+                    meta.advice_column(),
+                    meta.advice_column(),
+                    meta.advice_column(),
+                ];
+
+                // ...and only 3 for the compact one.
+                let compact_advices = [
+                    meta.advice_column(),
+                    meta.advice_column(),
+                    meta.advice_column(),
+                ];
+
+                Config {
+                    swap: CondSwapChip::<F>::configure(meta, advices),
+                    compact_swap: CompactCondSwapChip::<F>::configure(meta, compact_advices),
+                }
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), Error> {
+                let chip = CondSwapChip::<F>::construct(config.swap.clone());
+                let a = chip.load_private(layouter.namespace(|| "a"), config.swap.a, self.a)?;
+                let b = chip.load_private(layouter.namespace(|| "b"), config.swap.b, self.b)?;
+                let swapped_pair = chip.swap(layouter.namespace(|| "swap"), (a, b), self.swap)?;
+
+                let compact_chip = CompactCondSwapChip::<F>::construct(config.compact_swap.clone());
+                let a = compact_chip.load_private(
+                    layouter.namespace(|| "compact a"),
+                    config.compact_swap.a,
+                    self.a,
+                )?;
+                let b = compact_chip.load_private(
+                    layouter.namespace(|| "compact b"),
+                    config.compact_swap.b,
+                    self.b,
+                )?;
+                let compact_swapped_pair =
+                    compact_chip.swap(layouter.namespace(|| "compact swap"), (a, b), self.swap)?;
+
+                // Both chips must produce the same pair.
+                assert_eq!(swapped_pair.0.value(), compact_swapped_pair.0.value());
+                assert_eq!(swapped_pair.1.value(), compact_swapped_pair.1.value());
+
+                Ok(())
+            }
+        }
+
+        for swap in [true, false] {
+            let circuit: MyCircuit<Base> = MyCircuit {
+                a: Some(Base::rand()),
+                b: Some(Base::rand()),
+                swap: Some(swap),
+            };
+            let prover =
+                MockProver::<Base>::run(4, &circuit, vec![]).expect("mock should not fail");
+            assert_eq!(prover.verify(), Ok(()));
+        }
+    }
+}
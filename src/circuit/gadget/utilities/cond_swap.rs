@@ -44,7 +44,7 @@ impl<F: FieldExt> Chip<F> for CondSwapChip<F> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 pub struct CondSwapConfig {
     pub q_swap: Selector,
     pub a: Column<Advice>,
@@ -0,0 +1,218 @@
+use crate::halo2::pasta::Fp;
+use ff::PrimeField;
+
+use crate::public_inputs::PublicInputs;
+
+/// Errors returned when decoding public inputs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputError {
+    /// The word at `index` is not the canonical encoding of a field element.
+    NonCanonical { index: usize },
+}
+
+/// Decodes the `[root, nullifier, vote_hash]` public inputs from the words of a
+/// tally contract event, emitted as `nullifier, root, vote_hash` big-endian
+/// 32-byte words. Words that are not below the field modulus are rejected.
+pub fn public_inputs_from_event_words(words: &[[u8; 32]; 3]) -> Result<PublicInputs, InputError> {
+    let decode = |index: usize| {
+        let mut repr = words[index];
+        repr.reverse();
+        Option::from(Fp::from_repr(repr)).ok_or(InputError::NonCanonical { index })
+    };
+
+    Ok(PublicInputs {
+        census_root: decode(1)?,
+        nullifier: decode(0)?,
+        vote_hash: decode(2)?,
+    })
+}
+
+/// Encodes the `[root, nullifier, vote_hash]` public inputs as tally contract
+/// event words, see [`public_inputs_from_event_words`].
+pub fn public_inputs_to_event_words(public: &PublicInputs) -> [[u8; 32]; 3] {
+    [
+        to_event_word(public.nullifier),
+        to_event_word(public.census_root),
+        to_event_word(public.vote_hash),
+    ]
+}
+
+/// Encodes a field element as a big-endian 32-byte EVM word.
+fn to_event_word(value: Fp) -> [u8; 32] {
+    let mut word = value.to_repr();
+    word.reverse();
+    word
+}
+
+/// Encodes the nullifiers as EVM words for a contract expecting them unique and
+/// sorted in ascending order. Words compare as big-endian unsigned integers,
+/// so the words are sorted bytewise, which also orders the field elements by
+/// value.
+pub fn prepare_nullifiers_for_submission(nullifiers: &[Fp]) -> Vec<[u8; 32]> {
+    let mut words: Vec<[u8; 32]> = nullifiers.iter().map(|n| to_event_word(*n)).collect();
+    words.sort_unstable();
+    words.dedup();
+    words
+}
+
+/// Errors returned when parsing a field element.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// The string is empty or has a character that is not a hex digit.
+    InvalidHex,
+    /// The string has more than the 64 hex digits of 32 bytes.
+    TooLong,
+    /// The value is not below the field modulus.
+    NonCanonical,
+}
+
+/// Parses a field element from a big-endian hex string, as used on the JS/EVM
+/// side, with or without a `0x` prefix. Strings shorter than 64 digits are
+/// zero-padded on the left.
+pub fn fp_from_hex(hex: &str) -> Result<Fp, ParseError> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex).as_bytes();
+    if digits.is_empty() {
+        return Err(ParseError::InvalidHex);
+    }
+    if digits.len() > 64 {
+        return Err(ParseError::TooLong);
+    }
+
+    // fill the little-endian repr from the least significant digit
+    let mut repr = [0u8; 32];
+    for (n, digit) in digits.iter().rev().enumerate() {
+        let nibble = (*digit as char)
+            .to_digit(16)
+            .ok_or(ParseError::InvalidHex)? as u8;
+        repr[n / 2] |= nibble << (4 * (n % 2));
+    }
+    fp_from_bytes_le(&repr)
+}
+
+/// Formats a field element as a `0x`-prefixed, 64-digit big-endian hex string,
+/// the format read by [`fp_from_hex`].
+pub fn fp_to_hex(value: Fp) -> String {
+    let mut hex = String::from("0x");
+    for byte in to_event_word(value).iter() {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+/// Reads a field element from its little-endian `to_repr` bytes, rejecting
+/// values that are not below the field modulus.
+pub fn fp_from_bytes_le(bytes: &[u8; 32]) -> Result<Fp, ParseError> {
+    Option::from(Fp::from_repr(*bytes)).ok_or(ParseError::NonCanonical)
+}
+
+/// Returns the little-endian `to_repr` bytes of a field element.
+pub fn fp_to_bytes_le(value: Fp) -> [u8; 32] {
+    value.to_repr()
+}
+
+/// Formats the `[root, nullifier, vote_hash]` public inputs as decimal strings,
+/// in that same order, as taken by gnark/groth16 style toolchains.
+pub fn public_inputs_to_decimal_strings(public: &[Fp]) -> Vec<String> {
+    public
+        .iter()
+        .map(|value| bigint::U256::from_little_endian(&value.to_repr()).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::generate_test_data;
+
+    #[test]
+    fn event_words_test() {
+        let (_, public) = generate_test_data::<3>();
+
+        let words = public_inputs_to_event_words(&public);
+        assert_eq!(words[1], {
+            let mut root = public.census_root.to_repr();
+            root.reverse();
+            root
+        });
+        assert_eq!(public_inputs_from_event_words(&words), Ok(public));
+
+        // -1 + 1, the modulus itself, is not canonical
+        let mut words = words;
+        let max = PublicInputs {
+            vote_hash: -Fp::one(),
+            ..public
+        };
+        words[2] = public_inputs_to_event_words(&max)[2];
+        words[2][31] += 1;
+        assert_eq!(
+            public_inputs_from_event_words(&words),
+            Err(InputError::NonCanonical { index: 2 })
+        );
+    }
+
+    #[test]
+    fn decimal_strings_test() {
+        let (_, public) = generate_test_data::<3>();
+        assert_eq!(
+            public_inputs_to_decimal_strings(&public.to_vec()),
+            vec![
+                "10684430648958953151396432774765348122056624296768296902297999255773442030931",
+                "22245401134204873455006697732030746297686052053342215313599700663010319467366",
+                "1",
+            ]
+        );
+    }
+
+    #[test]
+    fn fp_hex_test() {
+        let value = Fp::from(0x1234);
+        let hex = fp_to_hex(value);
+        assert_eq!(hex.len(), 66);
+        assert!(hex.ends_with("0000001234"));
+        assert_eq!(fp_from_hex(&hex), Ok(value));
+        assert_eq!(fp_from_hex("1234"), Ok(value));
+        assert_eq!(fp_from_hex("0x00ABcd"), Ok(Fp::from(0xabcd)));
+        assert_eq!(fp_to_hex(-Fp::one()), format!("{:?}", -Fp::one()));
+        assert_eq!(fp_from_hex(&fp_to_hex(-Fp::one())), Ok(-Fp::one()));
+
+        assert_eq!(fp_from_hex(""), Err(ParseError::InvalidHex));
+        assert_eq!(fp_from_hex("0x"), Err(ParseError::InvalidHex));
+        assert_eq!(fp_from_hex("0x12g4"), Err(ParseError::InvalidHex));
+        assert_eq!(fp_from_hex(&"1".repeat(65)), Err(ParseError::TooLong));
+        assert_eq!(
+            fp_from_hex(&format!("0x{}", "f".repeat(64))),
+            Err(ParseError::NonCanonical)
+        );
+
+        // the modulus is -1 + 1
+        let mut modulus = fp_to_bytes_le(-Fp::one());
+        assert_eq!(fp_from_bytes_le(&modulus), Ok(-Fp::one()));
+        modulus[0] += 1;
+        assert_eq!(fp_from_bytes_le(&modulus), Err(ParseError::NonCanonical));
+        assert_eq!(fp_to_bytes_le(Fp::from(258))[..2], [2, 1]);
+    }
+
+    #[test]
+    fn prepare_nullifiers_test() {
+        let big = -Fp::one();
+        let words = prepare_nullifiers_for_submission(&[
+            Fp::from(258),
+            big,
+            Fp::from(3),
+            Fp::from(258),
+            Fp::from(3),
+        ]);
+
+        let mut expected = vec![[0u8; 32]; 2];
+        expected[0][31] = 3;
+        expected[1][30] = 1;
+        expected[1][31] = 2;
+        let public = PublicInputs {
+            census_root: Fp::zero(),
+            nullifier: big,
+            vote_hash: Fp::zero(),
+        };
+        expected.push(public_inputs_to_event_words(&public)[0]);
+        assert_eq!(words, expected);
+    }
+}
@@ -0,0 +1,137 @@
+//! Versioned test vectors for the ports of the census and nullifier
+//! derivations to other languages, which should reproduce the hex strings of
+//! [`TEST_VECTOR_HEX`] from its inputs byte for byte. Field elements are in the
+//! big-endian hex format of [`fp_to_hex`](crate::encoding::fp_to_hex).
+//!
+//! The inputs are the ones of [`generate_test_data`](crate::utils::generate_test_data).
+//! The vectors are never changed within a version: a change to the hashes
+//! bumps [`VERSION`] instead.
+
+use crate::halo2::pasta::Fp;
+use crate::merkle::MerkleTree;
+use crate::utils::{nullifier, secret_to_public_key};
+
+/// The version of the test vectors, bumped whenever their values change.
+pub const VERSION: u32 = 1;
+
+/// The census depth of the test vector.
+pub const DEPTH: usize = 3;
+
+/// The inputs of a franchise proof and the public values derived from them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestVector {
+    pub secret_key: Fp,
+    pub process_id: [Fp; 2],
+    pub vote_hash: Fp,
+    pub depth: usize,
+    /// The `(sibling, sibling_is_right)` census path, as in
+    /// [`MerkleTree::witness`].
+    pub witness: Vec<(Fp, bool)>,
+    pub public_key: Fp,
+    pub expected_root: Fp,
+    pub expected_nullifier: Fp,
+}
+
+/// The hex forms of a [`TestVector`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestVectorHex {
+    pub secret_key: &'static str,
+    pub process_id: [&'static str; 2],
+    pub vote_hash: &'static str,
+    pub depth: usize,
+    pub witness: &'static [(&'static str, bool)],
+    pub public_key: &'static str,
+    pub expected_root: &'static str,
+    pub expected_nullifier: &'static str,
+}
+
+/// The test vector of [`VERSION`], as computed by [`test_vector`].
+pub const TEST_VECTOR_HEX: TestVectorHex = TestVectorHex {
+    secret_key: "0x0000000000000000000000000000000000000000000000000000000000000008",
+    process_id: [
+        "0x0000000000000000000000000000000000000000000000000000000000000006",
+        "0x0000000000000000000000000000000000000000000000000000000000000007",
+    ],
+    vote_hash: "0x0000000000000000000000000000000000000000000000000000000000000001",
+    depth: DEPTH,
+    witness: &[
+        (
+            "0x0000000000000000000000000000000000000000000000000000000000000000",
+            true,
+        ),
+        (
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+            false,
+        ),
+        (
+            "0x0000000000000000000000000000000000000000000000000000000000000002",
+            true,
+        ),
+    ],
+    public_key: "0x0bb7c5823823bb2088a238ca8f106722b47cb9585d1ef59edb8ef93827a3d6eb",
+    expected_root: "0x179f2c66cc0fe3d8791282ca5d550e09928d3fa33bef40b7ebd10ca4aa893d53",
+    expected_nullifier: "0x312e736b1d71b9bf28dbfb9a23e3b7f1d55c53aea3775d752d0a226cf0f50366",
+};
+
+/// Computes the test vector with the primitives of the crate.
+pub fn test_vector() -> TestVector {
+    let secret_key = Fp::from(8);
+    let process_id = [Fp::from(6), Fp::from(7)];
+    let public_key = secret_to_public_key(secret_key);
+    let witness: Vec<(Fp, bool)> = (0..DEPTH as u64)
+        .map(|n| (Fp::from(n), n % 2 == 0))
+        .collect();
+
+    TestVector {
+        secret_key,
+        process_id,
+        vote_hash: Fp::from(1),
+        depth: DEPTH,
+        expected_root: MerkleTree::compute_root(public_key, &witness),
+        expected_nullifier: nullifier(secret_key, process_id),
+        witness,
+        public_key,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoding::fp_to_hex;
+    use crate::utils::generate_test_data;
+
+    #[test]
+    fn test_vector_hex() {
+        let vector = test_vector();
+        let hex = TEST_VECTOR_HEX;
+        assert_eq!(hex.secret_key, fp_to_hex(vector.secret_key));
+        assert_eq!(hex.process_id[0], fp_to_hex(vector.process_id[0]));
+        assert_eq!(hex.process_id[1], fp_to_hex(vector.process_id[1]));
+        assert_eq!(hex.vote_hash, fp_to_hex(vector.vote_hash));
+        assert_eq!(hex.depth, vector.depth);
+        assert_eq!(
+            hex.witness
+                .iter()
+                .map(|(sibling, right)| (sibling.to_string(), *right))
+                .collect::<Vec<_>>(),
+            vector
+                .witness
+                .iter()
+                .map(|(sibling, right)| (fp_to_hex(*sibling), *right))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(hex.public_key, fp_to_hex(vector.public_key));
+        assert_eq!(hex.expected_root, fp_to_hex(vector.expected_root));
+        assert_eq!(hex.expected_nullifier, fp_to_hex(vector.expected_nullifier));
+    }
+
+    #[test]
+    fn test_vector_matches_test_data() {
+        let vector = test_vector();
+        let (circuit, public) = generate_test_data::<DEPTH>();
+        assert_eq!(public.census_root, vector.expected_root);
+        assert_eq!(public.nullifier, vector.expected_nullifier);
+        assert_eq!(public.vote_hash, vector.vote_hash);
+        assert_eq!(circuit.public_inputs(), Some(public));
+    }
+}
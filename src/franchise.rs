@@ -2,36 +2,723 @@
 
 use crate::halo2::{
     circuit::{Layouter, SimpleFloorPlanner},
-    pasta::Fp,
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+    pasta::{EqAffine, Fp},
+    plonk::{
+        verify_proof, Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error,
+        Fixed, FloorPlanner, Instance, Selector, VerifyingKey,
+    },
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Challenge255},
+};
+#[cfg(not(feature = "verify-only"))]
+use crate::halo2::{
+    plonk::{create_proof, ProvingKey},
+    transcript::Blake2bWrite,
 };
 
 use crate::circuit::gadget::poseidon::{Hash, Pow5T3Chip, Pow5T3Config, StateWord, Word};
+#[cfg(feature = "compact-swap")]
+use crate::circuit::gadget::utilities::compact_cond_swap::{
+    CompactCondSwapChip as SwapChip, CompactCondSwapConfig as SwapConfig,
+};
+use crate::circuit::gadget::utilities::cond_swap::CondSwapInstructions;
+#[cfg(not(feature = "compact-swap"))]
 use crate::circuit::gadget::utilities::cond_swap::{
-    CondSwapChip, CondSwapConfig, CondSwapInstructions,
+    CondSwapChip as SwapChip, CondSwapConfig as SwapConfig,
 };
 use crate::circuit::gadget::utilities::{CellValue, Var};
-use crate::primitives::poseidon::{ConstantLength, P128Pow5T3};
+use crate::encoding::fp_from_bytes_le;
+use crate::merkle::{ImportMode, MerkleError};
+use crate::primitives::poseidon::{self, ConstantLength, P128Pow5T3};
+use crate::proof::Proof;
+use crate::public_inputs::PublicInputs;
+use crate::utils::{
+    domain_nullifier, generate_circuit_inputs_with_mode, link_tag, nullifier_commitment,
+    prefixed_leaf, secret_to_public_key, weighted_leaf, LINK_DOMAIN,
+};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use ff::{Field, PrimeField};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use std::convert::TryInto;
+use std::hash::{Hash as _, Hasher};
+use std::io;
+
+/// With the `serde` feature, the circuit (de)serializes with the field elements
+/// as their canonical `to_repr` bytes and the missing witnesses as `null`.
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FranchiseCircuit<const LVL: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::bool_array_option"))]
     pub pri_index: Option<[bool; LVL]>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_array_option"))]
     pub pri_siblings: Option<[Fp; LVL]>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
     pub pri_secret_key: Option<Fp>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_array_option"))]
     pub pub_processid: Option<[Fp; 2]>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
     pub pub_votehash: Option<Fp>,
+    /// Expose `Poseidon(root, Poseidon(nullifier, vote_hash))` as the single
+    /// public input instead of the three values.
+    pub expose_commitment: bool,
+    /// Hash of an external message (e.g. a meta-transaction) the proof is bound
+    /// to, exposed as the public input following the others when
+    /// `expose_message_hash` is set. It is not folded into the nullifier, which
+    /// must stay unique per voter and process.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub pub_message_hash: Option<Fp>,
+    pub expose_message_hash: bool,
+    /// When set, the census leaf is `Poseidon(prefix, public_key)` instead of
+    /// the public key, see [`prefixed_leaf`](crate::utils::prefixed_leaf).
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub leaf_prefix: Option<Fp>,
+    /// Nullifier received from an external system, constrained to be equal to
+    /// the computed nullifier when `check_claimed_nullifier` is set. It stays
+    /// private: proving fails for a wrong claim, without the verifier seeing
+    /// it, so it also works along with `expose_nullifier_commitment`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub pri_claimed_nullifier: Option<Fp>,
+    pub check_claimed_nullifier: bool,
+    /// Salt of the nullifier commitment. When `expose_nullifier_commitment` is
+    /// set, `Poseidon(nullifier, salt)` takes the place of the nullifier in the
+    /// public inputs, see [`nullifier_commitment`](crate::utils::nullifier_commitment).
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub pri_nullifier_salt: Option<Fp>,
+    pub expose_nullifier_commitment: bool,
+    /// Expose the [`link_tag`](crate::utils::link_tag) of the secret key as the
+    /// last public input. The tag is the same for all the proofs of a key, in
+    /// any process, so it makes the votes of a voter linkable by anyone: it is
+    /// meant for elections where that is intended, e.g. to revoke duplicates.
+    pub expose_link_tag: bool,
+    /// Expose the voter's public key as the public input following the root,
+    /// nullifier and vote hash, or their commitment, so that the census
+    /// membership can be cross-checked externally. It identifies the voter.
+    pub expose_public_key: bool,
+    /// Voting weight of the voter in a weighted census. When `weighted` is set,
+    /// the census leaf is `Poseidon(public_key, weight)`, see
+    /// [`weighted_leaf`](crate::utils::weighted_leaf), and the weight is exposed
+    /// as a public input for the tally.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub pri_weight: Option<Fp>,
+    pub weighted: bool,
+    /// Domain separation tag of the nullifier, for keys used across contract
+    /// instances. When set, the nullifier is `Poseidon(secret_key,
+    /// Poseidon(process_id_hash, domain))`, see
+    /// [`domain_nullifier`](crate::utils::domain_nullifier). It is fixed in the
+    /// circuit, like `leaf_prefix`; `None` keeps the nullifier without domain.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_fp::fp_option"))]
+    pub pub_domain: Option<Fp>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Hash)]
 pub struct FranchiseConfig {
     hash: Pow5T3Config<Fp>,
-    swap: CondSwapConfig,
+    swap: SwapConfig,
     instance: Column<Instance>,
 }
 
+/// A proof and the public inputs it is claimed to prove, see
+/// [`FranchiseCircuit::verify_batch_detailed`].
+#[derive(Clone, Debug)]
+pub struct BatchRecord {
+    pub public: Vec<Fp>,
+    pub proof: Proof,
+}
+
+/// Errors of the franchise operations that are not plain halo2 errors.
+#[derive(Debug)]
+pub enum FranchiseError {
+    /// The stored data was written for a circuit with another layout, see
+    /// [`FranchiseCircuit::compatibility_hash`].
+    ConfigMismatch,
+    /// The stored verifying key is not the key of the circuit.
+    VkMismatch,
+    /// The named witness is not set, see [`FranchiseCircuit::missing_fields`].
+    MissingWitness(&'static str),
+    Io(io::Error),
+    Halo2(Error),
+}
+
+/// A [`Hasher`] feeding the layout data to BLAKE2b, see
+/// [`FranchiseCircuit::compatibility_hash`]. Integers are hashed as little
+/// endian `u64`s, for the hash not to depend on the platform.
+struct LayoutHasher(blake2b_simd::State);
+
+impl Hasher for LayoutHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0.update(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        let hash = self.0.finalize();
+        u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+    }
+}
+
+impl FranchiseConfig {
+    fn compatibility_hash(&self, depth: usize) -> [u8; 32] {
+        let mut hasher = LayoutHasher(
+            blake2b_simd::Params::new()
+                .hash_length(32)
+                .personal(b"FranchiseConfig_")
+                .to_state(),
+        );
+        depth.hash(&mut hasher);
+        self.hash(&mut hasher);
+
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(hasher.0.finalize().as_bytes());
+        bytes
+    }
+}
+
+/// Size figures of the circuit constraint system, to compare the circuit
+/// complexity across versions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstraintSummary {
+    /// The maximum degree of the constraints, including the permutation
+    /// argument.
+    pub degree: usize,
+    /// The number of rows reserved for blinding factors.
+    pub blinding_factors: usize,
+    /// The minimum number of rows of the circuit, excluding assigned rows.
+    pub minimum_rows: usize,
+}
+
+/// An [`Assignment`] keeping only the number of rows used, see
+/// [`FranchiseCircuit::assigned_rows`]. The values are not computed.
+#[derive(Default)]
+struct RowCounter {
+    rows: usize,
+}
+
+impl RowCounter {
+    fn use_row(&mut self, row: usize) -> Result<(), Error> {
+        self.rows = self.rows.max(row + 1);
+        Ok(())
+    }
+}
+
+impl Assignment<Fp> for RowCounter {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row)
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Option<Fp>, Error> {
+        Ok(None)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<Fp>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row)
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<Fp>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row)
+    }
+
+    fn copy(
+        &mut self,
+        _: Column<Any>,
+        left_row: usize,
+        _: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        self.use_row(left_row.max(right_row))
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        _: usize,
+        _: Option<Assigned<Fp>>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
 impl<const LVL: usize> FranchiseCircuit<LVL> {
-    fn hash(
+    /// Returns a fingerprint of the circuit layout: the depth, the column and
+    /// selector indices of the gadgets and the Poseidon parameters. The keys of
+    /// a circuit are only usable with circuits of the same fingerprint, see
+    /// [`check_vk`](crate::keys::check_vk).
+    pub fn compatibility_hash() -> [u8; 32] {
+        Self::configure(&mut ConstraintSystem::default()).compatibility_hash(LVL)
+    }
+
+    /// Returns the number of rows the floor planner lays out for the circuit
+    /// with the modes set, instance rows included. The witnesses are not
+    /// needed, and the blinding rows are not counted.
+    pub fn assigned_rows(&self) -> usize {
+        let mut meta = ConstraintSystem::default();
+        let config = Self::configure(&mut meta);
+        // the constants column enabled in `configure`
+        let constants = vec![config.hash.rc_b[0]];
+
+        let mut counter = RowCounter::default();
+        SimpleFloorPlanner::synthesize(&mut counter, &self.without_witnesses(), config, constants)
+            .expect("the layout doesn't depend on the witnesses");
+        counter.rows
+    }
+
+    pub fn constraint_summary() -> ConstraintSummary {
+        let mut meta = ConstraintSystem::default();
+        Self::configure(&mut meta);
+
+        ConstraintSummary {
+            degree: meta.degree(),
+            blinding_factors: meta.blinding_factors(),
+            minimum_rows: meta.minimum_rows(),
+        }
+    }
+
+    /// Returns the names of the witness fields that are not set, which would
+    /// make synthesis fail with `Error::Synthesis`, and
+    /// [`prove`](Self::prove) with `FranchiseError::MissingWitness`.
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.pri_index.is_none() {
+            missing.push("pri_index");
+        }
+        if self.pri_siblings.is_none() {
+            missing.push("pri_siblings");
+        }
+        if self.pri_secret_key.is_none() {
+            missing.push("pri_secret_key");
+        }
+        if self.pub_processid.is_none() {
+            missing.push("pub_processid");
+        }
+        if self.pub_votehash.is_none() {
+            missing.push("pub_votehash");
+        }
+        if self.expose_message_hash && self.pub_message_hash.is_none() {
+            missing.push("pub_message_hash");
+        }
+        if self.check_claimed_nullifier && self.pri_claimed_nullifier.is_none() {
+            missing.push("pri_claimed_nullifier");
+        }
+        if self.weighted && self.pri_weight.is_none() {
+            missing.push("pri_weight");
+        }
+        if self.expose_nullifier_commitment && self.pri_nullifier_salt.is_none() {
+            missing.push("pri_nullifier_salt");
+        }
+        missing
+    }
+
+    /// Returns the number of public inputs of the circuit: the root, nullifier
+    /// and vote hash, or their commitment, followed by the public key, the
+    /// weight, the message hash and the link tag if exposed.
+    fn public_inputs_len(&self) -> usize {
+        let len = if self.expose_commitment { 1 } else { 3 };
+        len + self.expose_public_key as usize
+            + self.weighted as usize
+            + self.expose_message_hash as usize
+            + self.expose_link_tag as usize
+    }
+
+    /// Creates a proof for the circuit with the `public` inputs, returning the
+    /// proof bytes of a Blake2b transcript.
+    ///
+    /// Fails with `FranchiseError::MissingWitness` naming the first witness
+    /// missing, see [`FranchiseCircuit::missing_fields`], and with
+    /// `Error::InvalidInstances` if `public` doesn't have as many values as the
+    /// circuit exposes (`[root, nullifier, vote_hash]` by default).
+    #[cfg(not(feature = "verify-only"))]
+    pub fn prove(
         &self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        public: &[Fp],
+    ) -> Result<Proof, FranchiseError> {
+        if public.len() != self.public_inputs_len() {
+            return Err(FranchiseError::Halo2(Error::InvalidInstances));
+        }
+        if let Some(field) = self.missing_fields().first().copied() {
+            return Err(FranchiseError::MissingWitness(field));
+        }
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof(
+            params,
+            pk,
+            std::slice::from_ref(self),
+            &[&[public]],
+            &mut transcript,
+        )
+        .map_err(FranchiseError::Halo2)?;
+        Ok(Proof::from_bytes(transcript.finalize()))
+    }
+
+    /// Creates the proofs of a batch of circuits in the default layout in
+    /// parallel, each with its own transcript and all sharing the proving key,
+    /// returning the result of [`FranchiseCircuit::prove`] for each of them.
+    #[cfg(all(feature = "rayon", not(feature = "verify-only")))]
+    pub fn prove_batch(
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        circuits: &[(FranchiseCircuit<LVL>, PublicInputs)],
+    ) -> Vec<Result<Proof, FranchiseError>> {
+        use rayon::prelude::*;
+
+        circuits
+            .par_iter()
+            .map(|(circuit, public)| circuit.prove(params, pk, &public.to_vec()))
+            .collect()
+    }
+
+    /// Verifies a proof created with [`FranchiseCircuit::prove`] against the
+    /// `public` inputs, returning whether it is valid. Malformed proof bytes
+    /// are reported as the halo2 `Error` instead of panicking.
+    pub fn verify(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        public: &[Fp],
+        proof: &Proof,
+    ) -> Result<bool, Error> {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof.as_bytes());
+        let guard = verify_proof(
+            params,
+            vk,
+            params.empty_msm(),
+            &[&[public]],
+            &mut transcript,
+        )?;
+        Ok(guard.use_challenges().eval())
+    }
+
+    /// Verifies a proof of a circuit with `expose_commitment` set against its
+    /// single public input, the hash of the `[root, nullifier, vote_hash]`
+    /// public inputs computed by [`PublicInputs::commitment`].
+    /// Verifier contracts can then take that one value instead of the three.
+    pub fn verify_with_public_hash(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        public_hash: Fp,
+        proof: &Proof,
+    ) -> Result<bool, Error> {
+        Self::verify(params, vk, &[public_hash], proof)
+    }
+
+    /// Checks that two proofs of the same statement, e.g. received from
+    /// redundant provers, are both valid for the `public` inputs of a circuit
+    /// in the default layout. The proofs are not expected to be equal: proving
+    /// is randomized, so two proofs of the same vote differ byte for byte.
+    ///
+    /// Fails with `Error::ConstraintSystemFailure` if either proof is not
+    /// valid, and with the error of [`FranchiseCircuit::verify`] if either is
+    /// malformed.
+    pub fn both_valid(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        first: &Proof,
+        second: &Proof,
+        public: &PublicInputs,
+    ) -> Result<(), Error> {
+        let public = public.to_vec();
+        for proof in [first, second] {
+            if !Self::verify(params, vk, &public, proof)? {
+                return Err(Error::ConstraintSystemFailure);
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies a proof against public inputs received as their little-endian
+    /// `to_repr` bytes from an untrusted submitter, as
+    /// [`FranchiseCircuit::verify`] does once they are decoded. Each value must
+    /// be canonical, i.e. below the field modulus: a non-canonical encoding
+    /// reduces to a valid value, so accepting it would let the same nullifier be
+    /// submitted with different bytes.
+    ///
+    /// Fails with `Error::InvalidInstances` if a value is not canonical, with
+    /// `Error::ConstraintSystemFailure` if the proof is not valid, and with the
+    /// error of [`FranchiseCircuit::verify`] if it is malformed.
+    pub fn verify_strict(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        proof: &Proof,
+        public_bytes: &[[u8; 32]],
+    ) -> Result<(), Error> {
+        let public = public_bytes
+            .iter()
+            .map(|bytes| fp_from_bytes_le(bytes).map_err(|_| Error::InvalidInstances))
+            .collect::<Result<Vec<_>, _>>()?;
+        if Self::verify(params, vk, &public, proof)? {
+            Ok(())
+        } else {
+            Err(Error::ConstraintSystemFailure)
+        }
+    }
+
+    /// Verifies a proof of a circuit in the default layout for the `nullifier`
+    /// and `vote_hash`, accepting any of the last `max_age` census roots of
+    /// `accepted_roots`, ordered from the oldest to the most recent. With a
+    /// census open to registrations, this accepts honest proofs made against a
+    /// root that was replaced while they were in flight.
+    ///
+    /// Fails with `Error::ConstraintSystemFailure` if the proof is not valid for
+    /// any of those roots, and with the error of [`FranchiseCircuit::verify`]
+    /// if it is malformed.
+    pub fn verify_with_root_window(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        proof: &Proof,
+        nullifier: Fp,
+        vote_hash: Fp,
+        accepted_roots: &[Fp],
+        max_age: usize,
+    ) -> Result<(), Error> {
+        let window = &accepted_roots[accepted_roots.len().saturating_sub(max_age)..];
+        // most likely the latest root, so try them from the most recent
+        for root in window.iter().rev() {
+            let public = PublicInputs {
+                census_root: *root,
+                nullifier,
+                vote_hash,
+            };
+            if Self::verify(params, vk, &public.to_vec(), proof)? {
+                return Ok(());
+            }
+        }
+        Err(Error::ConstraintSystemFailure)
+    }
+
+    /// Verifies a proof of a circuit in the default layout for the `public`
+    /// inputs, rejecting it before the costly verification if its vote hash is
+    /// not one of the `allowed` ones, e.g. the
+    /// [`allowed_votes`](crate::utils::ElectionConfig::allowed_votes) of an
+    /// election with a fixed set of options.
+    ///
+    /// Fails with `Error::ConstraintSystemFailure` if the vote is not allowed
+    /// or the proof is not valid, and with the error of
+    /// [`FranchiseCircuit::verify`] if it is malformed.
+    pub fn verify_with_allowed_votes(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        proof: &Proof,
+        public: &PublicInputs,
+        allowed: &[Fp],
+    ) -> Result<(), Error> {
+        if !allowed.contains(&public.vote_hash) {
+            return Err(Error::ConstraintSystemFailure);
+        }
+        if Self::verify(params, vk, &public.to_vec(), proof)? {
+            Ok(())
+        } else {
+            Err(Error::ConstraintSystemFailure)
+        }
+    }
+
+    /// Verifies each record, returning one result per record so that the valid
+    /// ones can be accepted even if others fail. A proof that doesn't verify is
+    /// reported as `Error::ConstraintSystemFailure`, malformed proofs as the
+    /// error of [`FranchiseCircuit::verify`].
+    pub fn verify_batch_detailed(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        records: &[BatchRecord],
+    ) -> Vec<Result<(), Error>> {
+        records
+            .iter()
+            .map(
+                |record| match Self::verify(params, vk, &record.public, &record.proof) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(Error::ConstraintSystemFailure),
+                    Err(err) => Err(err),
+                },
+            )
+            .collect()
+    }
+
+    /// Verifies the proofs of circuits in the default layout with a single
+    /// multiscalar multiplication: the MSM of each proof is scaled by a random
+    /// factor and added to one accumulator, which is evaluated once instead of
+    /// once per proof. If the batch fails, the MSMs are evaluated one by one so
+    /// that the result still tells which proofs are invalid.
+    ///
+    /// The factors are derived from a hash of all the items, so they can't be
+    /// chosen by a prover. A malformed proof fails the whole call with the error
+    /// of [`FranchiseCircuit::verify`], see
+    /// [`FranchiseCircuit::verify_batch_detailed`] to isolate those.
+    pub fn verify_batch(
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        items: &[(PublicInputs, Proof)],
+    ) -> Result<Vec<bool>, Error> {
+        let mut seed = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"FranchiseBatch__")
+            .to_state();
+        for (public, proof) in items {
+            for value in public.to_vec() {
+                seed.update(value.to_repr().as_ref());
+            }
+            seed.update(&(proof.as_bytes().len() as u64).to_le_bytes());
+            seed.update(proof.as_bytes());
+        }
+        let mut rng = ChaCha20Rng::from_seed(seed.finalize().as_bytes().try_into().unwrap());
+
+        let mut batch = params.empty_msm();
+        let mut msms = Vec::with_capacity(items.len());
+        for (public, proof) in items {
+            let public = public.to_vec();
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof.as_bytes());
+            let msm = verify_proof(
+                params,
+                vk,
+                params.empty_msm(),
+                &[&[&public]],
+                &mut transcript,
+            )?
+            .use_challenges();
+
+            let mut scaled = msm.clone();
+            scaled.scale(Fp::random(&mut rng));
+            batch.add_msm(&scaled);
+            msms.push(msm);
+        }
+
+        if batch.eval() {
+            Ok(vec![true; items.len()])
+        } else {
+            Ok(msms.into_iter().map(|msm| msm.eval()).collect())
+        }
+    }
+
+    /// Recomputes off-circuit the root, nullifier and vote hash determined by
+    /// the witnesses, or `None` if any witness is missing. The nullifier is
+    /// replaced by its commitment when `expose_nullifier_commitment` is set.
+    /// These are the public inputs of the default layout, see
+    /// [`instance_values`](Self::instance_values) for the other modes.
+    pub fn public_inputs(&self) -> Option<PublicInputs> {
+        self.public_inputs_and_key()
+            .map(|(public_inputs, _)| public_inputs)
+    }
+
+    /// Recomputes off-circuit the instance values determined by the witnesses,
+    /// in the order the circuit exposes them, or `None` if any witness is
+    /// missing: the [`public_inputs`](Self::public_inputs), or their commitment
+    /// when `expose_commitment` is set, followed by the public key, the weight,
+    /// the message hash and the link tag if exposed.
+    pub fn instance_values(&self) -> Option<Vec<Fp>> {
+        let (public_inputs, public_key) = self.public_inputs_and_key()?;
+        let mut public = if self.expose_commitment {
+            vec![public_inputs.commitment()]
+        } else {
+            public_inputs.to_vec()
+        };
+        if self.expose_public_key {
+            public.push(public_key);
+        }
+        if self.weighted {
+            public.push(self.pri_weight?);
+        }
+        if self.expose_message_hash {
+            public.push(self.pub_message_hash?);
+        }
+        if self.expose_link_tag {
+            public.push(link_tag(self.pri_secret_key?));
+        }
+
+        Some(public)
+    }
+
+    fn public_inputs_and_key(&self) -> Option<(PublicInputs, Fp)> {
+        let hash = |left: Fp, right: Fp| {
+            poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([left, right])
+        };
+
+        let secret_key = self.pri_secret_key?;
+        let public_key = secret_to_public_key(secret_key);
+        let leaf = if self.weighted {
+            weighted_leaf(public_key, self.pri_weight?)
+        } else {
+            public_key
+        };
+        let mut root = match self.leaf_prefix {
+            Some(prefix) => prefixed_leaf(prefix, leaf),
+            None => leaf,
+        };
+        for (sibling, swap) in self.pri_siblings?.iter().zip(self.pri_index?.iter()) {
+            root = if *swap {
+                hash(*sibling, root)
+            } else {
+                hash(root, *sibling)
+            };
+        }
+
+        let mut nullifier = domain_nullifier(secret_key, self.pub_processid?, self.pub_domain);
+        if self.expose_nullifier_commitment {
+            nullifier = nullifier_commitment(nullifier, self.pri_nullifier_salt?);
+        }
+
+        let public_inputs = PublicInputs {
+            census_root: root,
+            nullifier,
+            vote_hash: self.pub_votehash?,
+        };
+        Some((public_inputs, public_key))
+    }
+
+    fn hash(
         config: &FranchiseConfig,
         mut layouter: impl Layouter<Fp>,
         values: [CellValue<Fp>; 2],
@@ -61,6 +748,67 @@ impl<const LVL: usize> FranchiseCircuit<LVL> {
         Ok(cell_value)
     }
 
+    /// Hashes three values with `ConstantLength<3>`, absorbed in two blocks of the
+    /// rate-2 sponge, the second one padded with a zero. It matches
+    /// [`poseidon_hash3`](crate::utils::poseidon_hash3) off-circuit.
+    fn hash3(
+        config: &FranchiseConfig,
+        mut layouter: impl Layouter<Fp>,
+        values: [CellValue<Fp>; 3],
+    ) -> Result<CellValue<Fp>, Error> {
+        let hash_chip = Pow5T3Chip::construct(config.hash.clone());
+
+        let hasher: Hash<
+            Fp,
+            Pow5T3Chip<Fp>,
+            P128Pow5T3,
+            ConstantLength<3_usize>,
+            3_usize,
+            2_usize,
+        > = Hash::init(
+            hash_chip,
+            layouter.namespace(|| "init"),
+            ConstantLength::<3>,
+        )?;
+
+        let words =
+            values.map(|value| Word::from_inner(StateWord::new(value.cell(), value.value())));
+
+        let hashed = hasher.hash(layouter.namespace(|| "hash"), words)?;
+
+        let cell_value = CellValue::new(hashed.inner().var, hashed.inner().value);
+
+        Ok(cell_value)
+    }
+
+    /// Assigns one level of the Merkle path: loads `sibling`, orders it with
+    /// `current` according to `index_bit` and hashes the pair, returning the
+    /// node of the upper level.
+    pub(crate) fn assign_merkle_level(
+        config: &FranchiseConfig,
+        mut layouter: impl Layouter<Fp>,
+        current: CellValue<Fp>,
+        sibling: Option<Fp>,
+        index_bit: Option<bool>,
+    ) -> Result<CellValue<Fp>, Error> {
+        let sibling = Self::load_private_input(
+            layouter.namespace(|| "load witness"),
+            "sibling",
+            config.swap.b,
+            sibling,
+        )?;
+
+        let swap_chip = SwapChip::<Fp>::construct(config.swap.clone());
+
+        let (left, right) = swap_chip.swap(
+            layouter.namespace(|| "mt swap"),
+            (current, sibling),
+            index_bit,
+        )?;
+
+        Self::hash(config, layouter.namespace(|| "mt hash"), [left, right])
+    }
+
     fn merkle_tree(
         &self,
         config: &FranchiseConfig,
@@ -68,28 +816,19 @@ impl<const LVL: usize> FranchiseCircuit<LVL> {
         mut root: CellValue<Fp>,
     ) -> Result<CellValue<Fp>, Error> {
         for n in 0..LVL {
-            let leaf = self.load_private_input(
-                layouter.namespace(|| "load witness"),
-                config.swap.b,
+            root = Self::assign_merkle_level(
+                config,
+                layouter.namespace(|| "mt level"),
+                root,
                 self.pri_siblings.map(|v| v[n]),
-            )?;
-
-            let swap_chip = CondSwapChip::<Fp>::construct(config.swap.clone());
-
-            let (left, right) = swap_chip.swap(
-                layouter.namespace(|| "mt swap"),
-                (root, leaf),
                 self.pri_index.map(|v| v[n]),
             )?;
-
-            root = self.hash(config, layouter.namespace(|| "mt hash"), [left, right])?;
         }
 
         Ok(root)
     }
 
     fn load_constant(
-        &self,
         config: &FranchiseConfig,
         mut layouter: impl Layouter<Fp>,
         constant: Fp,
@@ -114,20 +853,16 @@ impl<const LVL: usize> FranchiseCircuit<LVL> {
     }
 
     fn load_private_input(
-        &self,
         mut layouter: impl Layouter<Fp>,
+        name: &str,
         column: Column<Advice>,
         value: Option<Fp>,
     ) -> Result<CellValue<Fp>, Error> {
         let cell = layouter.assign_region(
-            || "load private input",
+            || name,
             |mut region| {
-                let cell = region.assign_advice(
-                    || String::from("load private"),
-                    column,
-                    0,
-                    || value.ok_or(Error::Synthesis),
-                )?;
+                let cell =
+                    region.assign_advice(|| name, column, 0, || value.ok_or(Error::Synthesis))?;
 
                 Ok(CellValue::new(cell, value))
             },
@@ -142,7 +877,18 @@ impl<const LVL: usize> Circuit<Fp> for FranchiseCircuit<LVL> {
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            expose_commitment: self.expose_commitment,
+            expose_message_hash: self.expose_message_hash,
+            leaf_prefix: self.leaf_prefix,
+            check_claimed_nullifier: self.check_claimed_nullifier,
+            expose_nullifier_commitment: self.expose_nullifier_commitment,
+            expose_link_tag: self.expose_link_tag,
+            expose_public_key: self.expose_public_key,
+            weighted: self.weighted,
+            pub_domain: self.pub_domain,
+            ..Self::default()
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
@@ -167,14 +913,31 @@ impl<const LVL: usize> Circuit<Fp> for FranchiseCircuit<LVL> {
 
         meta.enable_constant(rc_b[0]);
 
+        // the swap and the hashes are assigned in disjoint regions with their
+        // own selectors, so with `shared-columns` the swap chip reuses the
+        // Poseidon columns: 5 advice columns instead of 9 (4 instead of 7 with
+        // `compact-swap`), but its regions can't be laid out alongside the
+        // hashes anymore, adding rows, see `recommended_k`
+        #[cfg(feature = "shared-columns")]
+        let mut shared = vec![state[0], state[1], state[2], partial_sbox].into_iter();
+        #[cfg(not(feature = "shared-columns"))]
+        let mut shared = vec![].into_iter();
+        let mut advice_column = || shared.next().unwrap_or_else(|| meta.advice_column());
+
+        #[cfg(not(feature = "compact-swap"))]
         let swap_advices = [
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
+            advice_column(),
+            advice_column(),
+            advice_column(),
+            advice_column(),
+            advice_column(),
         ];
 
+        // the compact swap chip lays out the swapped pair in the row below the
+        // inputs, saving two advice columns
+        #[cfg(feature = "compact-swap")]
+        let swap_advices = [advice_column(), advice_column(), advice_column()];
+
         for s_a in swap_advices {
             meta.enable_equality(s_a.into());
         }
@@ -183,7 +946,7 @@ impl<const LVL: usize> Circuit<Fp> for FranchiseCircuit<LVL> {
         meta.enable_equality(instance.into());
 
         Self::Config {
-            swap: CondSwapChip::configure(meta, swap_advices),
+            swap: SwapChip::configure(meta, swap_advices),
             hash: Pow5T3Chip::configure(meta, P128Pow5T3, state, partial_sbox, rc_a, rc_b),
             instance,
         }
@@ -194,51 +957,394 @@ impl<const LVL: usize> Circuit<Fp> for FranchiseCircuit<LVL> {
         config: Self::Config,
         mut layouter: impl Layouter<Fp>,
     ) -> Result<(), Error> {
-        let one = self.load_constant(&config, layouter.namespace(|| "load ONE"), Fp::one())?;
+        let one = Self::load_constant(&config, layouter.namespace(|| "load ONE"), Fp::one())?;
+
+        let process_id_0 = Self::load_private_input(
+            layouter.namespace(|| "load process_id[0]"),
+            "process_id[0]",
+            config.swap.a,
+            self.pub_processid.map(|v| v[0]),
+        )?;
+
+        let process_id_1 = Self::load_private_input(
+            layouter.namespace(|| "load process_id[1]"),
+            "process_id[1]",
+            config.swap.a,
+            self.pub_processid.map(|v| v[1]),
+        )?;
+
+        let secret_key = Self::load_private_input(
+            layouter.namespace(|| "load secret key"),
+            "secret key",
+            config.swap.a,
+            self.pri_secret_key,
+        )?;
+
+        let vote_hash = Self::load_private_input(
+            layouter.namespace(|| "load vote hash"),
+            "vote hash",
+            config.swap.a,
+            self.pub_votehash,
+        )?;
+
+        let public_key = Self::hash(
+            &config,
+            layouter.namespace(|| "hash secret key"),
+            [one, secret_key],
+        )?;
+
+        let process_id_hash = Self::hash(
+            &config,
+            layouter.namespace(|| "hash process_id"),
+            [process_id_0, process_id_1],
+        )?;
+
+        let process_id_hash = match self.pub_domain {
+            Some(domain) => {
+                let domain =
+                    Self::load_constant(&config, layouter.namespace(|| "load domain"), domain)?;
+                Self::hash(
+                    &config,
+                    layouter.namespace(|| "hash process_id with domain"),
+                    [process_id_hash, domain],
+                )?
+            }
+            None => process_id_hash,
+        };
+
+        let nullifier = Self::hash(
+            &config,
+            layouter.namespace(|| "nullifier"),
+            [secret_key, process_id_hash],
+        )?;
+
+        if self.check_claimed_nullifier {
+            let claimed_nullifier = Self::load_private_input(
+                layouter.namespace(|| "load claimed nullifier"),
+                "claimed nullifier",
+                config.swap.a,
+                self.pri_claimed_nullifier,
+            )?;
+
+            layouter.assign_region(
+                || "nullifier == claimed nullifier",
+                |mut region| region.constrain_equal(nullifier.cell(), claimed_nullifier.cell()),
+            )?;
+        }
+
+        let nullifier = if self.expose_nullifier_commitment {
+            let salt = Self::load_private_input(
+                layouter.namespace(|| "load nullifier salt"),
+                "nullifier salt",
+                config.swap.a,
+                self.pri_nullifier_salt,
+            )?;
+
+            Self::hash(
+                &config,
+                layouter.namespace(|| "nullifier commitment"),
+                [nullifier, salt],
+            )?
+        } else {
+            nullifier
+        };
+
+        let weight = if self.weighted {
+            Some(Self::load_private_input(
+                layouter.namespace(|| "load weight"),
+                "weight",
+                config.swap.a,
+                self.pri_weight,
+            )?)
+        } else {
+            None
+        };
+
+        let leaf = match weight {
+            Some(weight) => Self::hash(
+                &config,
+                layouter.namespace(|| "hash weighted leaf"),
+                [public_key, weight],
+            )?,
+            None => public_key,
+        };
+
+        let leaf = match self.leaf_prefix {
+            Some(prefix) => {
+                let prefix = Self::load_constant(
+                    &config,
+                    layouter.namespace(|| "load leaf prefix"),
+                    prefix,
+                )?;
+                Self::hash(&config, layouter.namespace(|| "hash leaf"), [prefix, leaf])?
+            }
+            None => leaf,
+        };
+
+        let root = self.merkle_tree(&config, layouter.namespace(|| "mt"), leaf)?;
+
+        let next_row = if self.expose_commitment {
+            let nullifier_vote_hash = Self::hash(
+                &config,
+                layouter.namespace(|| "hash nullifier and vote hash"),
+                [nullifier, vote_hash],
+            )?;
+
+            let commitment = Self::hash(
+                &config,
+                layouter.namespace(|| "public inputs commitment"),
+                [root, nullifier_vote_hash],
+            )?;
+
+            // expose public inputs commitment as public_input[0]
+            layouter.constrain_instance(commitment.cell(), config.instance, 0)?;
+
+            1
+        } else {
+            // expose census root as public_input[0]
+            layouter.constrain_instance(root.cell(), config.instance, 0)?;
+
+            // expose nullifier as public_input[1]
+            layouter.constrain_instance(nullifier.cell(), config.instance, 1)?;
+
+            // expose vote hash public_input[2]
+            layouter.constrain_instance(vote_hash.cell(), config.instance, 2)?;
+
+            3
+        };
+
+        // expose public key after the census public inputs
+        let next_row = if self.expose_public_key {
+            layouter.constrain_instance(public_key.cell(), config.instance, next_row)?;
+
+            next_row + 1
+        } else {
+            next_row
+        };
+
+        // expose weight for the tally
+        let next_row = match weight {
+            Some(weight) => {
+                layouter.constrain_instance(weight.cell(), config.instance, next_row)?;
+
+                next_row + 1
+            }
+            None => next_row,
+        };
+
+        // expose message hash after the other public inputs
+        let next_row = if self.expose_message_hash {
+            let message_hash = Self::load_private_input(
+                layouter.namespace(|| "load message hash"),
+                "message hash",
+                config.swap.a,
+                self.pub_message_hash,
+            )?;
+
+            layouter.constrain_instance(message_hash.cell(), config.instance, next_row)?;
+
+            next_row + 1
+        } else {
+            next_row
+        };
+
+        // expose link tag as the last public input
+        if self.expose_link_tag {
+            let link_domain = Self::load_constant(
+                &config,
+                layouter.namespace(|| "load link domain"),
+                Fp::from(LINK_DOMAIN),
+            )?;
+
+            let link_tag = Self::hash(
+                &config,
+                layouter.namespace(|| "link tag"),
+                [secret_key, link_domain],
+            )?;
+
+            layouter.constrain_instance(link_tag.cell(), config.instance, next_row)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a [`FranchiseCircuit`] proving the default public inputs from the
+/// voter's inputs and Merkle witness, deriving `pri_index` and `pri_siblings`
+/// from the [`MerkleTree::witness`](crate::merkle::MerkleTree::witness) format.
+#[derive(Clone, Debug, Default)]
+pub struct FranchiseCircuitBuilder<const LVL: usize> {
+    secret_key: Option<Fp>,
+    process_id: Option<[Fp; 2]>,
+    vote_hash: Option<Fp>,
+    witness: Option<Vec<(Fp, bool)>>,
+    import_mode: ImportMode,
+}
+
+/// Reasons for [`FranchiseCircuitBuilder::build`] to fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuilderError {
+    /// The named input was not set.
+    Missing(&'static str),
+    /// The witness can't be imported with the builder's [`ImportMode`].
+    Witness(MerkleError),
+}
+
+impl<const LVL: usize> FranchiseCircuitBuilder<LVL> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn secret_key(mut self, secret_key: Fp) -> Self {
+        self.secret_key = Some(secret_key);
+        self
+    }
+
+    pub fn process_id(mut self, process_id: [Fp; 2]) -> Self {
+        self.process_id = Some(process_id);
+        self
+    }
+
+    pub fn vote_hash(mut self, vote_hash: Fp) -> Self {
+        self.vote_hash = Some(vote_hash);
+        self
+    }
+
+    /// Sets the witness of the voter's leaf, as returned by
+    /// [`MerkleTree::witness`](crate::merkle::MerkleTree::witness).
+    pub fn witness(mut self, witness: &[(Fp, bool)]) -> Self {
+        self.witness = Some(witness.to_vec());
+        self
+    }
+
+    /// Sets how a witness without `LVL` levels is handled, strictly rejecting
+    /// it by default.
+    pub fn import_mode(mut self, import_mode: ImportMode) -> Self {
+        self.import_mode = import_mode;
+        self
+    }
+
+    /// Returns the circuit and the nullifier of the voter.
+    pub fn build(self) -> Result<(FranchiseCircuit<LVL>, Fp), BuilderError> {
+        generate_circuit_inputs_with_mode(
+            self.secret_key.ok_or(BuilderError::Missing("secret_key"))?,
+            self.process_id.ok_or(BuilderError::Missing("process_id"))?,
+            self.vote_hash.ok_or(BuilderError::Missing("vote_hash"))?,
+            &self.witness.ok_or(BuilderError::Missing("witness"))?,
+            self.import_mode,
+        )
+        .map_err(BuilderError::Witness)
+    }
+}
+
+/// A [`FranchiseCircuit`] whose census depth is set at runtime instead of being
+/// the `LVL` const generic, for services that load the depth with the process.
+/// It only proves the default `[root, nullifier, vote_hash]` public inputs.
+///
+/// The circuit still depends on the depth, so does its verifying key: a service
+/// supporting several depths keeps one key per depth, generated from
+/// [`FranchiseCircuitDyn::new`], and has to pick the right one for each proof.
+/// The witnesses are `Vec`s whose length is only checked on synthesis, where
+/// `FranchiseCircuit` checks it at compile time.
+#[derive(Clone, Default)]
+pub struct FranchiseCircuitDyn {
+    pub depth: usize,
+    pub pri_index: Option<Vec<bool>>,
+    pub pri_siblings: Option<Vec<Fp>>,
+    pub pri_secret_key: Option<Fp>,
+    pub pub_processid: Option<[Fp; 2]>,
+    pub pub_votehash: Option<Fp>,
+}
+
+// the chips and the loading of values don't depend on the depth
+type Gadgets = FranchiseCircuit<0>;
+
+impl FranchiseCircuitDyn {
+    /// Creates a circuit of `depth` levels without witnesses, e.g. for key
+    /// generation.
+    pub fn new(depth: usize) -> Self {
+        FranchiseCircuitDyn {
+            depth,
+            ..Self::default()
+        }
+    }
+}
+
+impl Circuit<Fp> for FranchiseCircuitDyn {
+    type Config = FranchiseConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(self.depth)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        Gadgets::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let one = Gadgets::load_constant(&config, layouter.namespace(|| "load ONE"), Fp::one())?;
 
-        let process_id_0 = self.load_private_input(
+        let process_id_0 = Gadgets::load_private_input(
             layouter.namespace(|| "load process_id[0]"),
+            "process_id[0]",
             config.swap.a,
             self.pub_processid.map(|v| v[0]),
         )?;
 
-        let process_id_1 = self.load_private_input(
+        let process_id_1 = Gadgets::load_private_input(
             layouter.namespace(|| "load process_id[1]"),
+            "process_id[1]",
             config.swap.a,
             self.pub_processid.map(|v| v[1]),
         )?;
 
-        let secret_key = self.load_private_input(
+        let secret_key = Gadgets::load_private_input(
             layouter.namespace(|| "load secret key"),
+            "secret key",
             config.swap.a,
             self.pri_secret_key,
         )?;
 
-        let vote_hash = self.load_private_input(
+        let vote_hash = Gadgets::load_private_input(
             layouter.namespace(|| "load vote hash"),
+            "vote hash",
             config.swap.a,
             self.pub_votehash,
         )?;
 
-        let public_key = self.hash(
+        let public_key = Gadgets::hash(
             &config,
             layouter.namespace(|| "hash secret key"),
             [one, secret_key],
         )?;
 
-        let process_id_hash = self.hash(
+        let process_id_hash = Gadgets::hash(
             &config,
             layouter.namespace(|| "hash process_id"),
             [process_id_0, process_id_1],
         )?;
 
-        let nullifier = self.hash(
+        let nullifier = Gadgets::hash(
             &config,
             layouter.namespace(|| "nullifier"),
             [secret_key, process_id_hash],
         )?;
 
-        let root = self.merkle_tree(&config, layouter.namespace(|| "mt"), public_key)?;
+        let mut root = public_key;
+        for n in 0..self.depth {
+            root = Gadgets::assign_merkle_level(
+                &config,
+                layouter.namespace(|| "mt level"),
+                root,
+                self.pri_siblings.as_ref().and_then(|v| v.get(n).copied()),
+                self.pri_index.as_ref().and_then(|v| v.get(n).copied()),
+            )?;
+        }
 
         // expose census root as public_input[0]
         layouter.constrain_instance(root.cell(), config.instance, 0)?;
@@ -253,44 +1359,905 @@ impl<const LVL: usize> Circuit<Fp> for FranchiseCircuit<LVL> {
     }
 }
 
+/// Renders the column layout of a `FranchiseCircuit<LVL>` with `2^k` rows to
+/// the image at `path`, to see how much of the rows the regions take when
+/// choosing `k`: an SVG if `path` ends in `.svg`, otherwise a bitmap in the
+/// format of its extension, e.g. `.png`.
+#[cfg(feature = "dev-graph")]
+pub fn render_layout<const LVL: usize>(k: u32, path: &std::path::Path) -> std::io::Result<()> {
+    use plotters::prelude::*;
+
+    const SIZE: (u32, u32) = (1024, 768);
+
+    // no title or labels, so that no fonts are needed
+    fn render<DB: DrawingBackend, const LVL: usize>(
+        k: u32,
+        root: DrawingArea<DB, plotters::coord::Shift>,
+    ) -> std::io::Result<()> {
+        let to_io = |err| std::io::Error::other(format!("{:?}", err));
+
+        root.fill(&WHITE).map_err(to_io)?;
+        crate::halo2::dev::CircuitLayout::default()
+            .render(k as usize, &FranchiseCircuit::<LVL>::default(), &root)
+            .map_err(to_io)?;
+        root.present().map_err(to_io)
+    }
+
+    if path.extension() == Some("svg".as_ref()) {
+        render::<_, LVL>(k, SVGBackend::new(path, SIZE).into_drawing_area())
+    } else {
+        render::<_, LVL>(k, BitMapBackend::new(path, SIZE).into_drawing_area())
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::halo2::dev::CircuitLayout;
     use crate::halo2::dev::MockProver;
     use crate::halo2::pasta::Fp;
-    use plotters::prelude::*;
+    #[cfg(not(feature = "verify-only"))]
+    use crate::halo2::plonk::{keygen_pk, keygen_vk};
 
     use super::*;
-    use crate::utils::generate_test_data;
+    use crate::merkle::MerkleTreeBuilder;
+    use crate::testing;
+    use crate::utils::{
+        generate_circuit_inputs, generate_test_data, generate_test_data_with_public_key, nullifier,
+        open_nullifier_commitment, poseidon_hash2, poseidon_hash3, recommended_k,
+        recommended_k_for,
+    };
 
-    fn print_circuit<const LVL: usize>(circuit: FranchiseCircuit<LVL>, k: u32) {
-        let root = BitMapBackend::new("circuit-layout.png", (1024, 768)).into_drawing_area();
-        root.fill(&WHITE).unwrap();
-        let root = root.titled("Circuit Layout", ("sans-serif", 6)).unwrap();
+    fn mock_test<const LVL: usize>(k: u32) {
+        let (circuit, public) = generate_test_data::<LVL>();
+        testing::assert_valid(k, &circuit, &public.to_vec());
+    }
+
+    #[test]
+    fn test_franchise() {
+        mock_test::<3>(recommended_k::<3>());
+    }
+
+    #[test]
+    fn test_franchise_commitment() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        circuit.expose_commitment = true;
+        let commitment = public.commitment();
 
-        CircuitLayout::default()
-            .render(k as usize, &circuit, &root)
+        let prover = MockProver::run(9, &circuit, vec![vec![commitment]]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        let prover = MockProver::run(9, &circuit, vec![vec![commitment + Fp::one()]])
+            .expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_verify_with_public_hash() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        circuit.expose_commitment = true;
+        let public_hash = public.commitment();
+
+        let params: Params<EqAffine> = Params::new(9);
+        let empty_circuit = circuit.without_witnesses();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        assert_eq!(circuit.instance_values(), Some(vec![public_hash]));
+        let proof = circuit
+            .prove(&params, &pk, &circuit.instance_values().unwrap())
             .unwrap();
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_with_public_hash(
+                &params,
+                pk.get_vk(),
+                public_hash,
+                &proof
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_with_public_hash(
+                &params,
+                pk.get_vk(),
+                public_hash + Fp::one(),
+                &proof
+            ),
+            Ok(false)
+        );
     }
 
-    fn mock_test<const LVL: usize>(k: u32) {
-        let (circuit, mut public) = generate_test_data::<LVL>();
+    #[test]
+    fn test_franchise_message_hash() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        let mut public = public.to_vec();
+        circuit.pub_message_hash = Some(Fp::from(42));
+        circuit.expose_message_hash = true;
+        public.push(Fp::from(42));
+        assert_eq!(circuit.instance_values(), Some(public.clone()));
 
-        let prover = MockProver::run(k, &circuit, vec![public.clone()]).expect("cannot run mock");
+        let prover = MockProver::run(recommended_k::<3>(), &circuit, vec![public.clone()])
+            .expect("cannot run mock");
         assert_eq!(Ok(()), prover.verify());
 
-        for n in 0..public.len() {
-            public[n] += Fp::from(1);
-            assert!(MockProver::run(k, &circuit, vec![public.clone()])
-                .expect("cannot run mock")
-                .verify()
-                .is_err());
-            public[n] -= Fp::from(1);
+        public[3] = Fp::from(43);
+        let prover =
+            MockProver::run(recommended_k::<3>(), &circuit, vec![public]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_franchise_leaf_prefix() {
+        fn transform(public_key: Fp) -> Fp {
+            prefixed_leaf(Fp::from(99), public_key)
         }
+
+        let secret_key = Fp::from(8);
+        let mut builder = MerkleTreeBuilder::new(4).with_leaf_transform(transform);
+        builder.insert(Fp::from(5)).unwrap();
+        let index = builder.insert(secret_to_public_key(secret_key)).unwrap();
+        let tree = builder.build();
+
+        let (mut circuit, nullifier) = generate_circuit_inputs::<3>(
+            secret_key,
+            [Fp::from(6), Fp::from(7)],
+            Fp::from(1),
+            &tree.witness(index).unwrap(),
+        );
+        circuit.leaf_prefix = Some(Fp::from(99));
+        let public = vec![tree.root(), nullifier, Fp::from(1)];
+        assert_eq!(circuit.instance_values(), Some(public.clone()));
+
+        let prover = MockProver::run(9, &circuit, vec![public]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
     }
 
     #[test]
-    fn test_franchise() {
-        mock_test::<3>(8);
+    fn test_franchise_public_key() {
+        let (circuit, public) = generate_test_data_with_public_key::<3>(false);
+        assert_eq!(public.len(), 3);
+        assert_eq!(circuit.instance_values(), Some(public));
+
+        let (circuit, mut public) = generate_test_data_with_public_key::<3>(true);
+        assert_eq!(public[3], secret_to_public_key(Fp::from(8)));
+        assert_eq!(circuit.instance_values(), Some(public.clone()));
+
+        let prover = MockProver::run(recommended_k::<3>(), &circuit, vec![public.clone()])
+            .expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        public[3] += Fp::one();
+        let prover =
+            MockProver::run(recommended_k::<3>(), &circuit, vec![public]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_franchise_weighted() {
+        let secret_key = Fp::from(8);
+        let mut builder = MerkleTreeBuilder::new(4);
+        builder
+            .insert(weighted_leaf(
+                secret_to_public_key(Fp::from(9)),
+                Fp::from(2),
+            ))
+            .unwrap();
+        let index = builder
+            .insert(weighted_leaf(secret_to_public_key(secret_key), Fp::from(5)))
+            .unwrap();
+        let tree = builder.build();
+
+        let (mut circuit, nullifier) = generate_circuit_inputs::<3>(
+            secret_key,
+            [Fp::from(6), Fp::from(7)],
+            Fp::from(1),
+            &tree.witness(index).unwrap(),
+        );
+        circuit.weighted = true;
+        circuit.pri_weight = Some(Fp::from(5));
+        let mut public = vec![tree.root(), nullifier, Fp::from(1), Fp::from(5)];
+        assert_eq!(circuit.instance_values(), Some(public.clone()));
+
+        let prover = MockProver::run(9, &circuit, vec![public.clone()]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        // claiming another weight changes the leaf, which is not in the census
+        circuit.pri_weight = Some(Fp::from(6));
+        public[3] = Fp::from(6);
+        let prover = MockProver::run(9, &circuit, vec![public]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_franchise_domain() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        let secret_key = Fp::from(8);
+        let process_id = [Fp::from(6), Fp::from(7)];
+
+        let first = domain_nullifier(secret_key, process_id, Some(Fp::from(1)));
+        let second = domain_nullifier(secret_key, process_id, Some(Fp::from(2)));
+        assert_ne!(first, second);
+        assert_ne!(first, public.nullifier);
+        assert_eq!(
+            domain_nullifier(secret_key, process_id, None),
+            public.nullifier
+        );
+
+        circuit.pub_domain = Some(Fp::from(1));
+        let domain_public = vec![public.census_root, first, public.vote_hash];
+        assert_eq!(circuit.instance_values(), Some(domain_public.clone()));
+
+        let prover = MockProver::run(9, &circuit, vec![domain_public]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        let prover = MockProver::run(9, &circuit, vec![public.to_vec()]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_franchise_link_tag() {
+        let mut builder = MerkleTreeBuilder::new(4);
+        builder.insert(secret_to_public_key(Fp::from(8))).unwrap();
+        builder.insert(secret_to_public_key(Fp::from(9))).unwrap();
+        let tree = builder.build();
+
+        let vote = |index: usize, secret_key: u64, process_id: u64| {
+            let (mut circuit, _) = generate_circuit_inputs::<3>(
+                Fp::from(secret_key),
+                [Fp::from(process_id), Fp::from(7)],
+                Fp::from(1),
+                &tree.witness(index).unwrap(),
+            );
+            circuit.expose_link_tag = true;
+            let public = circuit.instance_values().unwrap();
+
+            let prover =
+                MockProver::run(9, &circuit, vec![public.clone()]).expect("cannot run mock");
+            assert_eq!(Ok(()), prover.verify());
+
+            public
+        };
+
+        let first = vote(0, 8, 6);
+        let second = vote(0, 8, 16);
+        let other_voter = vote(1, 9, 6);
+        assert_eq!(first[3], link_tag(Fp::from(8)));
+        assert_ne!(first[1], second[1]);
+        assert_eq!(first[3], second[3]);
+        assert_ne!(first[3], other_voter[3]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let (expected, public) = generate_test_data::<3>();
+
+        let witness: Vec<(Fp, bool)> = (0..3u64).map(|n| (Fp::from(n), n % 2 == 0)).collect();
+        let builder = FranchiseCircuitBuilder::<3>::new()
+            .secret_key(Fp::from(8))
+            .process_id([Fp::from(6), Fp::from(7)])
+            .vote_hash(Fp::from(1));
+        let (circuit, nullifier) = builder.clone().witness(&witness).build().unwrap();
+        assert_eq!(nullifier, public.nullifier);
+        assert_eq!(circuit.pri_index, expected.pri_index);
+        assert_eq!(circuit.pri_siblings, expected.pri_siblings);
+        assert_eq!(circuit.public_inputs(), Some(public));
+
+        assert_eq!(
+            builder.clone().build().err(),
+            Some(BuilderError::Missing("witness"))
+        );
+        assert_eq!(
+            builder.witness(&witness[..2]).build().err(),
+            Some(BuilderError::Witness(MerkleError::InvalidLength {
+                expected: 3,
+                actual: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn test_franchise_dyn() {
+        let secret_key = Fp::from(8);
+        let process_id = [Fp::from(6), Fp::from(7)];
+
+        for (depth, k) in [(3, recommended_k::<3>()), (5, recommended_k::<5>())] {
+            let mut builder = MerkleTreeBuilder::new(depth as u32 + 1);
+            builder.insert(Fp::from(5)).unwrap();
+            let index = builder.insert(secret_to_public_key(secret_key)).unwrap();
+            let tree = builder.build();
+            let witness = tree.witness(index).unwrap();
+
+            let circuit = FranchiseCircuitDyn {
+                depth,
+                pri_index: Some(witness.iter().map(|(_, direction)| !direction).collect()),
+                pri_siblings: Some(witness.iter().map(|(sibling, _)| *sibling).collect()),
+                pri_secret_key: Some(secret_key),
+                pub_processid: Some(process_id),
+                pub_votehash: Some(Fp::from(1)),
+            };
+            let public = vec![tree.root(), nullifier(secret_key, process_id), Fp::from(1)];
+
+            let prover =
+                MockProver::run(k, &circuit, vec![public.clone()]).expect("cannot run mock");
+            assert_eq!(Ok(()), prover.verify());
+
+            // the witness is shorter than the depth
+            let deeper = FranchiseCircuitDyn {
+                depth: depth + 1,
+                ..circuit
+            };
+            assert!(MockProver::run(k, &deeper, vec![public]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_assign_merkle_level() {
+        struct TwoLevels(FranchiseCircuit<2>);
+
+        impl Circuit<Fp> for TwoLevels {
+            type Config = FranchiseConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+
+            fn without_witnesses(&self) -> Self {
+                TwoLevels(self.0.without_witnesses())
+            }
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                FranchiseCircuit::<2>::configure(meta)
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                let circuit = &self.0;
+                let leaf = FranchiseCircuit::<2>::load_private_input(
+                    layouter.namespace(|| "load leaf"),
+                    "leaf",
+                    config.swap.a,
+                    Some(Fp::from(5)),
+                )?;
+
+                let mut manual = leaf;
+                for n in 0..2 {
+                    manual = FranchiseCircuit::<2>::assign_merkle_level(
+                        &config,
+                        layouter.namespace(|| "manual level"),
+                        manual,
+                        circuit.pri_siblings.map(|v| v[n]),
+                        circuit.pri_index.map(|v| v[n]),
+                    )?;
+                }
+
+                let looped = circuit.merkle_tree(&config, layouter.namespace(|| "mt"), leaf)?;
+                assert!(manual.value().is_some());
+                assert_eq!(manual.value(), looped.value());
+
+                Ok(())
+            }
+        }
+
+        let (circuit, _) = generate_test_data::<2>();
+        let prover =
+            MockProver::run(9, &TwoLevels(circuit), vec![vec![]]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+    }
+
+    #[test]
+    fn test_hash3() {
+        struct Hash3([Fp; 3]);
+
+        impl Circuit<Fp> for Hash3 {
+            type Config = FranchiseConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+
+            fn without_witnesses(&self) -> Self {
+                Hash3(self.0)
+            }
+
+            fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+                FranchiseCircuit::<0>::configure(meta)
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<Fp>,
+            ) -> Result<(), Error> {
+                let mut values = vec![];
+                for value in self.0.iter() {
+                    values.push(FranchiseCircuit::<0>::load_private_input(
+                        layouter.namespace(|| "load value"),
+                        "value",
+                        config.swap.a,
+                        Some(*value),
+                    )?);
+                }
+
+                let hashed = FranchiseCircuit::<0>::hash3(
+                    &config,
+                    layouter.namespace(|| "hash3"),
+                    [values[0], values[1], values[2]],
+                )?;
+                layouter.constrain_instance(hashed.cell(), config.instance, 0)
+            }
+        }
+
+        let values = [Fp::from(1), Fp::from(2), Fp::from(3)];
+        let expected = poseidon_hash3(values[0], values[1], values[2]);
+        assert_ne!(
+            expected,
+            poseidon_hash2(poseidon_hash2(values[0], values[1]), values[2])
+        );
+
+        let prover =
+            MockProver::run(8, &Hash3(values), vec![vec![expected]]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        let prover = MockProver::run(8, &Hash3(values), vec![vec![expected + Fp::one()]])
+            .expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[cfg(feature = "dev-graph")]
+    #[test]
+    fn test_render_layout() {
+        let path = std::env::temp_dir().join("franchise-test-layout.svg");
+        render_layout::<3>(recommended_k::<3>(), &path).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_constraint_summary() {
+        assert_eq!(
+            FranchiseCircuit::<3>::constraint_summary(),
+            ConstraintSummary {
+                degree: 6,
+                blinding_factors: 5,
+                minimum_rows: 8,
+            }
+        );
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_prove() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let public = public.to_vec();
+        let proof = circuit.prove(&params, &pk, &public).unwrap();
+        assert_eq!(
+            FranchiseCircuit::<3>::verify(&params, pk.get_vk(), &public, &proof),
+            Ok(true)
+        );
+        let opened = proof.seal().open().expect("the sealed proof is intact");
+        assert_eq!(
+            FranchiseCircuit::<3>::verify(&params, pk.get_vk(), &public, &opened),
+            Ok(true)
+        );
+
+        let mut other = public.clone();
+        other[2] += Fp::one();
+        assert_ne!(
+            FranchiseCircuit::<3>::verify(&params, pk.get_vk(), &other, &proof),
+            Ok(true)
+        );
+        let truncated = Proof::from_bytes(proof.as_bytes()[..10].to_vec());
+        assert!(FranchiseCircuit::<3>::verify(&params, pk.get_vk(), &public, &truncated).is_err());
+
+        let records = vec![
+            BatchRecord {
+                public: public.clone(),
+                proof: proof.clone(),
+            },
+            BatchRecord {
+                public: other,
+                proof: proof.clone(),
+            },
+            BatchRecord {
+                public: public.clone(),
+                proof: truncated,
+            },
+            BatchRecord {
+                public: public.clone(),
+                proof,
+            },
+        ];
+        let results = FranchiseCircuit::<3>::verify_batch_detailed(&params, pk.get_vk(), &records);
+        assert_eq!(
+            results.iter().map(Result::is_ok).collect::<Vec<_>>(),
+            vec![true, false, false, true]
+        );
+
+        assert!(matches!(
+            circuit.prove(&params, &pk, &public[..2]),
+            Err(FranchiseError::Halo2(Error::InvalidInstances))
+        ));
+        assert!(matches!(
+            FranchiseCircuit::<3>::default().prove(&params, &pk, &public),
+            Err(FranchiseError::MissingWitness("pri_index"))
+        ));
+        let unsalted = FranchiseCircuit {
+            expose_nullifier_commitment: true,
+            ..circuit.clone()
+        };
+        assert!(matches!(
+            unsalted.prove(&params, &pk, &public),
+            Err(FranchiseError::MissingWitness("pri_nullifier_salt"))
+        ));
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_verify_with_root_window() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let proof = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+
+        // the proof is against the third most recent root
+        let roots = [Fp::from(1), public.census_root, Fp::from(2), Fp::from(3)];
+        let verify = |roots: &[Fp], max_age| {
+            FranchiseCircuit::<3>::verify_with_root_window(
+                &params,
+                pk.get_vk(),
+                &proof,
+                public.nullifier,
+                public.vote_hash,
+                roots,
+                max_age,
+            )
+        };
+        assert_eq!(verify(&roots, 3), Ok(()));
+        assert_eq!(verify(&roots, 10), Ok(()));
+        assert_eq!(verify(&roots, 2), Err(Error::ConstraintSystemFailure));
+        assert_eq!(verify(&roots, 0), Err(Error::ConstraintSystemFailure));
+        assert_eq!(verify(&[], 3), Err(Error::ConstraintSystemFailure));
+
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_with_root_window(
+                &params,
+                pk.get_vk(),
+                &proof,
+                public.nullifier,
+                public.vote_hash + Fp::one(),
+                &roots,
+                3,
+            ),
+            Err(Error::ConstraintSystemFailure)
+        );
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_verify_with_allowed_votes() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let proof = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+        let verify = |proof: &Proof, public: &PublicInputs, allowed: &[Fp]| {
+            FranchiseCircuit::<3>::verify_with_allowed_votes(
+                &params,
+                pk.get_vk(),
+                proof,
+                public,
+                allowed,
+            )
+        };
+
+        let allowed = [
+            public.vote_hash + Fp::one(),
+            public.vote_hash,
+            public.vote_hash + Fp::from(2),
+        ];
+        assert_eq!(verify(&proof, &public, &allowed), Ok(()));
+        assert_eq!(
+            verify(&proof, &public, &allowed[..1]),
+            Err(Error::ConstraintSystemFailure)
+        );
+        assert_eq!(
+            verify(&proof, &public, &[]),
+            Err(Error::ConstraintSystemFailure)
+        );
+
+        // an allowed vote the proof is not for
+        let other = PublicInputs {
+            vote_hash: allowed[0],
+            ..public
+        };
+        assert_eq!(
+            verify(&proof, &other, &allowed),
+            Err(Error::ConstraintSystemFailure)
+        );
+
+        // a disallowed vote is rejected before reading the proof
+        let malformed = Proof::from_bytes(vec![0; 8]);
+        assert_eq!(
+            verify(&malformed, &public, &allowed[..1]),
+            Err(Error::ConstraintSystemFailure)
+        );
+        assert!(verify(&malformed, &public, &allowed).is_err());
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_verify_strict() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let proof = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+        let mut public_bytes: Vec<_> = public.to_vec().iter().map(Fp::to_repr).collect();
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_strict(&params, pk.get_vk(), &proof, &public_bytes),
+            Ok(())
+        );
+
+        // the nullifier plus the modulus, which reduces to the nullifier
+        let mut modulus = (-Fp::one()).to_repr();
+        modulus[0] += 1;
+        let mut carry = 0;
+        for (byte, modulus) in public_bytes[1].iter_mut().zip(modulus.iter()) {
+            let sum = *byte as u16 + *modulus as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(carry, 0);
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_strict(&params, pk.get_vk(), &proof, &public_bytes),
+            Err(Error::InvalidInstances)
+        );
+
+        let mut public_bytes: Vec<_> = public.to_vec().iter().map(Fp::to_repr).collect();
+        public_bytes[2] = (public.vote_hash + Fp::one()).to_repr();
+        assert_eq!(
+            FranchiseCircuit::<3>::verify_strict(&params, pk.get_vk(), &proof, &public_bytes),
+            Err(Error::ConstraintSystemFailure)
+        );
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_both_valid() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let first = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+        let second = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            FranchiseCircuit::<3>::both_valid(&params, pk.get_vk(), &first, &second, &public),
+            Ok(())
+        );
+
+        let mut voted = circuit;
+        voted.pub_votehash = Some(public.vote_hash + Fp::one());
+        let voted_public = voted.instance_values().unwrap();
+        let other = voted.prove(&params, &pk, &voted_public).unwrap();
+        assert_eq!(
+            FranchiseCircuit::<3>::both_valid(&params, pk.get_vk(), &first, &other, &public),
+            Err(Error::ConstraintSystemFailure)
+        );
+        assert_eq!(
+            FranchiseCircuit::<3>::both_valid(&params, pk.get_vk(), &other, &first, &public),
+            Err(Error::ConstraintSystemFailure)
+        );
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_verify_batch() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let proof = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+        let other = PublicInputs {
+            vote_hash: public.vote_hash + Fp::one(),
+            ..public
+        };
+
+        let items = vec![(public, proof.clone()), (public, proof.clone())];
+        let results = FranchiseCircuit::<3>::verify_batch(&params, pk.get_vk(), &items);
+        assert_eq!(results, Ok(vec![true, true]));
+
+        let items = vec![
+            (public, proof.clone()),
+            (other, proof.clone()),
+            (public, proof.clone()),
+        ];
+        let results = FranchiseCircuit::<3>::verify_batch(&params, pk.get_vk(), &items);
+        assert_eq!(results, Ok(vec![true, false, true]));
+
+        let truncated = Proof::from_bytes(proof.as_bytes()[..10].to_vec());
+        let items = vec![(public, proof), (public, truncated)];
+        assert!(FranchiseCircuit::<3>::verify_batch(&params, pk.get_vk(), &items).is_err());
+    }
+
+    #[cfg(all(feature = "rayon", not(feature = "verify-only")))]
+    #[test]
+    fn test_prove_batch() {
+        let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+        let empty_circuit = FranchiseCircuit::<3>::default();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let (circuit, public) = generate_test_data::<3>();
+        let mut voted = circuit.clone();
+        voted.pub_votehash = Some(public.vote_hash + Fp::one());
+        let voted_public = PublicInputs {
+            vote_hash: public.vote_hash + Fp::one(),
+            ..public
+        };
+        let batch = vec![
+            (circuit.clone(), public),
+            (voted, voted_public),
+            (FranchiseCircuit::default(), public),
+        ];
+
+        let proofs = FranchiseCircuit::<3>::prove_batch(&params, &pk, &batch);
+        assert_eq!(proofs.len(), 3);
+        assert!(matches!(
+            proofs[2],
+            Err(FranchiseError::MissingWitness("pri_index"))
+        ));
+        for ((_, public), proof) in batch.iter().zip(&proofs).take(2) {
+            let proof = proof.as_ref().expect("proof generation should not fail");
+            assert_eq!(
+                FranchiseCircuit::<3>::verify(&params, pk.get_vk(), &public.to_vec(), proof),
+                Ok(true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_franchise_claimed_nullifier() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        circuit.check_claimed_nullifier = true;
+
+        let k = recommended_k_for(&circuit);
+
+        circuit.pri_claimed_nullifier = Some(public.nullifier);
+        let prover = MockProver::run(k, &circuit, vec![public.to_vec()]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        circuit.pri_claimed_nullifier = Some(public.nullifier + Fp::one());
+        let prover = MockProver::run(k, &circuit, vec![public.to_vec()]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+
+        // the claim is checked against the nullifier hidden in the commitment
+        circuit.expose_nullifier_commitment = true;
+        circuit.pri_nullifier_salt = Some(Fp::from(0x5a17));
+        let committed = circuit.instance_values().unwrap();
+        let k = recommended_k_for(&circuit);
+        let prover =
+            MockProver::run(k, &circuit, vec![committed.clone()]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+
+        circuit.pri_claimed_nullifier = Some(public.nullifier);
+        let prover = MockProver::run(k, &circuit, vec![committed]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+    }
+
+    #[test]
+    fn test_franchise_nullifier_commitment() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        let salt = Fp::from(0x5a17);
+        circuit.expose_nullifier_commitment = true;
+        circuit.pri_nullifier_salt = Some(salt);
+
+        let commitment = nullifier_commitment(public.nullifier, salt);
+        let committed = vec![public.census_root, commitment, public.vote_hash];
+        assert_eq!(circuit.instance_values(), Some(committed.clone()));
+        assert!(open_nullifier_commitment(
+            commitment,
+            public.nullifier,
+            salt
+        ));
+        assert!(!open_nullifier_commitment(
+            commitment,
+            public.nullifier,
+            salt + Fp::one()
+        ));
+
+        let prover = MockProver::run(9, &circuit, vec![committed]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+
+        let prover = MockProver::run(9, &circuit, vec![public.to_vec()]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+
+        let wrong_salt = vec![
+            public.census_root,
+            nullifier_commitment(public.nullifier, salt + Fp::one()),
+            public.vote_hash,
+        ];
+        let prover = MockProver::run(9, &circuit, vec![wrong_salt]).expect("cannot run mock");
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        let (circuit, public) = generate_test_data::<3>();
+        assert_eq!(circuit.public_inputs(), Some(public));
+        assert_eq!(circuit.instance_values(), Some(public.to_vec()));
+        assert_eq!(FranchiseCircuit::<3>::default().public_inputs(), None);
+        assert_eq!(FranchiseCircuit::<3>::default().instance_values(), None);
+
+        let exposed = FranchiseCircuit {
+            expose_public_key: true,
+            ..circuit.clone()
+        };
+        assert_eq!(exposed.public_inputs(), Some(public));
+        let mut values = public.to_vec();
+        values.push(secret_to_public_key(circuit.pri_secret_key.unwrap()));
+        assert_eq!(exposed.instance_values(), Some(values));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let (circuit, public) = generate_test_data::<3>();
+        let json = serde_json::to_string(&circuit).expect("cannot serialize");
+        let decoded: FranchiseCircuit<3> = serde_json::from_str(&json).expect("cannot deserialize");
+        assert_eq!(decoded.pri_index, circuit.pri_index);
+        assert_eq!(decoded.pri_siblings, circuit.pri_siblings);
+        assert_eq!(decoded.pri_secret_key, circuit.pri_secret_key);
+        assert_eq!(decoded.public_inputs(), Some(public));
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+
+        let json = serde_json::to_value(circuit.without_witnesses()).unwrap();
+        assert!(json["pri_secret_key"].is_null());
+        assert!(json["pri_index"].is_null());
+        let decoded: FranchiseCircuit<3> = serde_json::from_value(json).unwrap();
+        assert!(decoded.pri_siblings.is_none());
+
+        let mut json = serde_json::to_value(&circuit).unwrap();
+        json["pri_siblings"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<FranchiseCircuit<3>>(json).is_err());
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let (mut circuit, public) = generate_test_data::<3>();
+        assert!(circuit.missing_fields().is_empty());
+
+        circuit.pri_secret_key = None;
+        assert_eq!(circuit.missing_fields(), vec!["pri_secret_key"]);
+        assert!(MockProver::run(8, &circuit, vec![public.to_vec()]).is_err());
+    }
+
+    #[test]
+    fn test_compatibility_hash() {
+        assert_eq!(
+            FranchiseCircuit::<3>::compatibility_hash(),
+            FranchiseCircuit::<3>::compatibility_hash()
+        );
+        assert_ne!(
+            FranchiseCircuit::<3>::compatibility_hash(),
+            FranchiseCircuit::<4>::compatibility_hash()
+        );
+
+        let config = FranchiseCircuit::<3>::configure(&mut ConstraintSystem::default());
+        let mut altered = config.clone();
+        std::mem::swap(&mut altered.swap.a, &mut altered.swap.b);
+        assert_ne!(config.compatibility_hash(3), altered.compatibility_hash(3));
     }
 }
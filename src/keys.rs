@@ -0,0 +1,141 @@
+use std::io::{self, Read, Write};
+
+use crate::franchise::{FranchiseCircuit, FranchiseError};
+use crate::halo2::{
+    pasta::EqAffine,
+    plonk::{keygen_vk, VerifyingKey},
+    poly::commitment::Params,
+};
+
+/// Writes the params, e.g. to ship them precomputed instead of running
+/// `Params::new` on every start.
+pub fn write_params(params: &Params<EqAffine>, mut w: impl Write) -> io::Result<()> {
+    params.write(&mut w)
+}
+
+/// Reads params written by [`write_params`]. Fails with
+/// `io::ErrorKind::InvalidData` if they are not for circuits of `2^k` rows.
+pub fn read_params(mut r: impl Read, k: u32) -> io::Result<Params<EqAffine>> {
+    // the params start with their k, as a little endian u32
+    let mut stored_k = [0u8; 4];
+    r.read_exact(&mut stored_k)?;
+    if u32::from_le_bytes(stored_k) != k {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "params are for k = {}, expected k = {}",
+                u32::from_le_bytes(stored_k),
+                k
+            ),
+        ));
+    }
+
+    Params::read(&mut (&stored_k[..]).chain(r))
+}
+
+/// Writes the verifying key of a `FranchiseCircuit<LVL>`, e.g. to publish the
+/// key of a deployment, after the
+/// [`compatibility_hash`](FranchiseCircuit::compatibility_hash) of the circuit.
+/// It can't be loaded back to skip `keygen_vk`, see [`check_vk`].
+pub fn write_vk<const LVL: usize>(
+    vk: &VerifyingKey<EqAffine>,
+    mut w: impl Write,
+) -> io::Result<()> {
+    w.write_all(&FranchiseCircuit::<LVL>::compatibility_hash())?;
+    vk.write(&mut w)
+}
+
+/// Regenerates the verifying key of `circuit` with `params` and compares it
+/// with the `stored` one [`write_vk`] wrote, returning the regenerated key.
+/// Fails with `FranchiseError::ConfigMismatch` if the key was written for
+/// another circuit layout, and with `FranchiseError::VkMismatch` if the keys
+/// differ.
+///
+/// The stored key is never deserialized, halo2's `VerifyingKey::read` can't
+/// read back the keys `keygen_vk` generates, so this costs a `keygen_vk` run.
+/// It only guards against verifying with the published key of another
+/// circuit, depth, mode or params.
+pub fn check_vk<const LVL: usize>(
+    mut stored: impl Read,
+    params: &Params<EqAffine>,
+    circuit: &FranchiseCircuit<LVL>,
+) -> Result<VerifyingKey<EqAffine>, FranchiseError> {
+    let mut hash = [0u8; 32];
+    stored.read_exact(&mut hash).map_err(FranchiseError::Io)?;
+    if hash != FranchiseCircuit::<LVL>::compatibility_hash() {
+        return Err(FranchiseError::ConfigMismatch);
+    }
+    let mut bytes = Vec::new();
+    stored.read_to_end(&mut bytes).map_err(FranchiseError::Io)?;
+
+    let vk = keygen_vk(params, circuit).map_err(FranchiseError::Halo2)?;
+    let mut expected = Vec::new();
+    vk.write(&mut expected).map_err(FranchiseError::Io)?;
+    if bytes != expected {
+        return Err(FranchiseError::VkMismatch);
+    }
+    Ok(vk)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::utils::recommended_k;
+
+    #[test]
+    fn test_params_bytes() {
+        let params: Params<EqAffine> = Params::new(4);
+        let mut bytes = Vec::new();
+        write_params(&params, &mut bytes).unwrap();
+
+        let read = read_params(Cursor::new(&bytes), 4).unwrap();
+        let mut read_bytes = Vec::new();
+        write_params(&read, &mut read_bytes).unwrap();
+        assert_eq!(bytes, read_bytes);
+
+        let err = read_params(Cursor::new(&bytes), 5).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_vk_bytes() {
+        let k = recommended_k::<3>();
+        let params: Params<EqAffine> = Params::new(k);
+        let vk = keygen_vk(&params, &FranchiseCircuit::<3>::default()).unwrap();
+        let mut bytes = Vec::new();
+        write_vk::<3>(&vk, &mut bytes).unwrap();
+
+        let circuit = FranchiseCircuit::<3>::default();
+        let read = check_vk(&bytes[..], &params, &circuit).unwrap();
+        let mut read_bytes = Vec::new();
+        write_vk::<3>(&read, &mut read_bytes).unwrap();
+        assert_eq!(bytes, read_bytes);
+        assert_eq!(format!("{:?}", read.pinned()), format!("{:?}", vk.pinned()));
+
+        for (bytes, params) in [
+            (&bytes[..bytes.len() - 1], &params),
+            (&bytes[..], &Params::new(k + 1)),
+        ] {
+            let err = check_vk(bytes, params, &circuit).unwrap_err();
+            assert!(matches!(err, FranchiseError::VkMismatch));
+        }
+        let err = check_vk(
+            &bytes[..],
+            &Params::new(recommended_k::<4>()),
+            &FranchiseCircuit::<4>::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, FranchiseError::ConfigMismatch));
+        let err = check_vk(&bytes[..16], &params, &circuit).unwrap_err();
+        assert!(matches!(err, FranchiseError::Io(_)));
+
+        let exposed = FranchiseCircuit::<3> {
+            expose_public_key: true,
+            ..Default::default()
+        };
+        let err = check_vk(&bytes[..], &params, &exposed).unwrap_err();
+        assert!(matches!(err, FranchiseError::VkMismatch));
+    }
+}
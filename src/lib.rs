@@ -7,6 +7,17 @@ pub use halo2_zcash as halo2;
 pub use halo2_adria0 as halo2;
 
 mod circuit;
+pub mod encoding;
+pub mod fixtures;
 pub mod franchise;
-mod primitives;
+pub mod keys;
+pub mod merkle;
+pub mod primitives;
+pub mod proof;
+pub mod public_inputs;
+#[cfg(feature = "serde")]
+mod serde_fp;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+pub mod transcript;
 pub mod utils;
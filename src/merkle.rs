@@ -0,0 +1,1594 @@
+use crate::halo2::pasta::Fp;
+use ff::PrimeField;
+use std::collections::HashMap;
+
+use crate::primitives::poseidon::{self, ConstantLength, Domain, Mds, P128Pow5T3, Spec};
+use crate::utils::poseidon_hash2;
+
+pub struct MerkleTreeBuilder {
+    depth: u32,
+    nodes: Vec<Fp>,
+    leaf_transform: fn(Fp) -> Fp,
+}
+
+pub struct MerkleTree<H: MerkleHasher = PoseidonHasher> {
+    depth: u32,
+    nodes: Vec<Fp>,
+    hasher: H,
+}
+
+/// The two-to-one hash combining sibling nodes into their parent in a
+/// [`MerkleTree`]. The circuit only proves membership in trees built with the
+/// default [`PoseidonHasher`]; other hashers are meant for integrations and
+/// debugging.
+pub trait MerkleHasher: Send + Sync {
+    fn hash(&self, left: Fp, right: Fp) -> Fp;
+}
+
+/// Hashes the nodes with Poseidon `P128Pow5T3` over `ConstantLength<2>`, as the
+/// circuit does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoseidonHasher;
+
+impl MerkleHasher for PoseidonHasher {
+    fn hash(&self, left: Fp, right: Fp) -> Fp {
+        poseidon_hash2(left, right)
+    }
+}
+
+/// Errors returned by the fallible [`MerkleTree`] operations. Building the
+/// tree, as well as [`MerkleTree::root`], [`MerkleTree::to_flat`] and the
+/// witness checks, can't fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MerkleError {
+    /// The number of nodes doesn't match the depth of the tree.
+    InvalidLength { expected: usize, actual: usize },
+    /// There are more leaves than the tree can hold.
+    Full { capacity: usize },
+    /// The leaf at `index` is not the voter's public key.
+    NotMember { index: usize },
+    /// The trees being compared have different depths.
+    DepthMismatch { expected: u32, actual: u32 },
+    /// The witness leads to the `actual` root instead of the `expected` one.
+    RootMismatch { expected: Fp, actual: Fp },
+    /// The leaf is not in the tree.
+    LeafNotFound,
+    /// There is no node at `index`, the tree has `len` of them.
+    IndexOutOfRange { index: usize, len: usize },
+    /// The depth is 0, a tree has at least the root level.
+    DepthTooSmall,
+    /// The depth is over the `max` supported by the operation.
+    DepthTooLarge { max: u32 },
+    /// The key has bits beyond the `depth - 1` of the path of a
+    /// [`SparseMerkleTree`] leaf.
+    KeyOutOfRange,
+    /// The bytes are not a tree serialized with [`MerkleTree::to_bytes`]: they
+    /// are truncated or hold a non-canonical node.
+    InvalidBytes,
+    /// [`build_election`] got a different number of `votes` than of `voters`.
+    VoteCountMismatch { voters: usize, votes: usize },
+}
+
+/// A leaf that differs between two trees, see [`MerkleTree::diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeafDiff {
+    pub index: usize,
+    pub old: Fp,
+    pub new: Fp,
+}
+
+/// A Merkle witness where the siblings that are roots of empty subtrees are
+/// omitted. Bit `n` of `empty_mask` is set when the sibling at level `n` is the
+/// empty hash of that level, see [`empty_hashes`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompactWitness {
+    pub siblings: Vec<Fp>,
+    pub directions: Vec<bool>,
+    pub empty_mask: u64,
+}
+
+impl CompactWitness {
+    /// Omits the siblings of `witness` equal to the `empty` hash of their level.
+    /// Fails if it has more than the 64 levels `empty_mask` can flag.
+    fn compress(witness: Vec<(Fp, bool)>, empty: &[Fp]) -> Result<Self, MerkleError> {
+        if witness.len() > 64 {
+            return Err(MerkleError::DepthTooLarge { max: 65 });
+        }
+        let mut compact = CompactWitness {
+            siblings: Vec::new(),
+            directions: Vec::new(),
+            empty_mask: 0,
+        };
+        for (n, (sibling, direction)) in witness.into_iter().enumerate() {
+            if sibling == empty[n] {
+                compact.empty_mask |= 1 << n;
+            } else {
+                compact.siblings.push(sibling);
+            }
+            compact.directions.push(direction);
+        }
+        Ok(compact)
+    }
+
+    /// Reconstitutes the dense witness, as returned by [`MerkleTree::witness`].
+    /// Returns `None` if the witness is malformed: it has more than 64 levels,
+    /// or not as many siblings as levels that aren't flagged as empty.
+    pub fn expand(&self) -> Option<Vec<(Fp, bool)>> {
+        let levels = self.directions.len();
+        let flagged = if levels < 64 {
+            self.empty_mask & ((1 << levels) - 1)
+        } else {
+            self.empty_mask
+        };
+        if levels > 64
+            || flagged != self.empty_mask
+            || self.siblings.len() != levels - flagged.count_ones() as usize
+        {
+            return None;
+        }
+
+        let empty = empty_hashes(levels as u32 + 1);
+        let mut siblings = self.siblings.iter();
+        self.directions
+            .iter()
+            .enumerate()
+            .map(|(n, direction)| {
+                let sibling = if self.empty_mask & (1 << n) != 0 {
+                    empty[n]
+                } else {
+                    *siblings.next()?
+                };
+                Some((sibling, *direction))
+            })
+            .collect()
+    }
+}
+
+/// Returns the hash of an empty subtree at each level of a tree with `depth`
+/// levels, starting with the (zero) leaf and ending with the empty root.
+pub fn empty_hashes(depth: u32) -> Vec<Fp> {
+    let mut hashes = vec![Fp::zero()];
+    for n in 1..depth as usize {
+        hashes.push(MerkleTreeBuilder::hash(hashes[n - 1], hashes[n - 1]));
+    }
+    hashes
+}
+
+impl MerkleTreeBuilder {
+    /// Creates a builder of a tree of `depth` levels. Panics if `depth` is 0,
+    /// see [`MerkleTree::from_leaf_iter`] for a fallible constructor.
+    pub fn new(depth: u32) -> Self {
+        let size = 2usize.pow(depth - 1);
+        Self {
+            depth,
+            nodes: Vec::with_capacity(2 * size - 1),
+            leaf_transform: |value| value,
+        }
+    }
+
+    /// Like [`MerkleTreeBuilder::new`], but only preallocating room for
+    /// `expected_leaves` leaves instead of the whole `2^depth - 1` nodes while
+    /// inserting. It saves nothing at build time: `build` still allocates the
+    /// full dense tree, whatever the number of leaves.
+    pub fn with_capacity_hint(depth: u32, expected_leaves: usize) -> Self {
+        Self {
+            depth,
+            nodes: Vec::with_capacity(expected_leaves),
+            leaf_transform: |value| value,
+        }
+    }
+
+    /// Applies `leaf_transform` to the inserted values to derive the leaves, for
+    /// censuses whose leaves are not the public keys themselves. By default the
+    /// values are inserted as is. See [`FranchiseCircuit::leaf_prefix`] for the
+    /// in-circuit counterpart of [`prefixed_leaf`].
+    pub fn with_leaf_transform(mut self, leaf_transform: fn(Fp) -> Fp) -> Self {
+        self.leaf_transform = leaf_transform;
+        self
+    }
+
+    /// Appends a leaf, returning its index, or fails if the `2^(depth-1)`
+    /// leaves of the tree are already used.
+    pub fn insert(&mut self, value: Fp) -> Result<usize, MerkleError> {
+        let capacity = match MerkleTree::max_leaves_for_depth(self.depth) {
+            Some(capacity) => capacity,
+            None if self.depth == 0 => return Err(MerkleError::DepthTooSmall),
+            None => return Err(MerkleError::DepthTooLarge { max: usize::BITS }),
+        };
+        if self.nodes.len() == capacity {
+            return Err(MerkleError::Full { capacity });
+        }
+        self.nodes.push((self.leaf_transform)(value));
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Appends the `values` leaves, failing at the first one that doesn't fit.
+    pub fn extend(&mut self, values: impl IntoIterator<Item = Fp>) -> Result<(), MerkleError> {
+        for value in values {
+            self.insert(value)?;
+        }
+        Ok(())
+    }
+
+    fn hash(first: Fp, second: Fp) -> Fp {
+        poseidon_hash2(first, second)
+    }
+
+    pub fn build(self) -> MerkleTree {
+        self.build_with_hasher(PoseidonHasher)
+    }
+
+    /// Like [`MerkleTreeBuilder::build`], but hashing the nodes with `hasher`.
+    pub fn build_with_hasher<H: MerkleHasher>(self, hasher: H) -> MerkleTree<H> {
+        let MerkleTreeBuilder {
+            depth, mut nodes, ..
+        } = self;
+
+        // fill with zeroes the unused leafs
+        let size = 2usize.pow(depth - 1);
+        if nodes.len() < size {
+            nodes.resize(size, Fp::zero());
+        }
+        nodes.reserve_exact(size - 1);
+
+        // compute the merkle tree nodes, level by level
+        let mut start = 0;
+        let mut len = size;
+        while len > 1 {
+            let parents = Self::hash_level(&hasher, &nodes[start..start + len]);
+            nodes.extend(parents);
+            start += len;
+            len /= 2;
+        }
+
+        MerkleTree {
+            depth,
+            nodes,
+            hasher,
+        }
+    }
+
+    /// Hashes each pair of sibling nodes of a level into their parent. The
+    /// pairs are independent, so they are hashed in parallel with the `rayon`
+    /// feature.
+    #[cfg(not(feature = "rayon"))]
+    fn hash_level<H: MerkleHasher>(hasher: &H, level: &[Fp]) -> Vec<Fp> {
+        level
+            .chunks(2)
+            .map(|pair| hasher.hash(pair[0], pair[1]))
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn hash_level<H: MerkleHasher>(hasher: &H, level: &[Fp]) -> Vec<Fp> {
+        use rayon::prelude::*;
+
+        level
+            .par_chunks(2)
+            .map(|pair| hasher.hash(pair[0], pair[1]))
+            .collect()
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    pub fn print_tree(&self) {
+        let mut pos = (self.nodes.len() - 1) as isize;
+        let mut lvl = 1;
+        while pos >= 0 {
+            for l in 0..lvl {
+                let s = format!("{:?}", self.nodes[(pos + l) as usize]);
+                print!("{} ", &s[60..66]);
+            }
+            println!();
+            pos -= lvl * 2;
+            lvl *= 2;
+        }
+    }
+
+    /// Returns the nodes of the tree as a flat array: first the leaves from left
+    /// to right, then each upper level in the same order, ending with the root.
+    /// The array has `2 * 2^(depth-1) - 1` nodes.
+    pub fn to_flat(&self) -> Vec<Fp> {
+        self.nodes.clone()
+    }
+
+    pub fn root(&self) -> Fp {
+        self.nodes[self.nodes.len() - 1]
+    }
+
+    /// Serializes the tree as its depth, as a little-endian `u32`, followed by
+    /// the 32-byte representation of each node in the [`MerkleTree::to_flat`]
+    /// ordering, so it can be stored and restored without rehashing.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + 32 * self.nodes.len());
+        bytes.extend_from_slice(&self.depth.to_le_bytes());
+        for node in &self.nodes {
+            bytes.extend_from_slice(&node.to_repr());
+        }
+        bytes
+    }
+
+    /// Replaces the leaf `old_leaf` by `new_leaf`, recomputing only the nodes
+    /// in its path, and returns its index. The leaf index is kept, so the
+    /// voter's witness must be regenerated against the new root.
+    pub fn rotate_key(&mut self, old_leaf: Fp, new_leaf: Fp) -> Result<usize, MerkleError> {
+        let index = self.index_of(old_leaf).ok_or(MerkleError::LeafNotFound)?;
+        self.update_leaf(index, new_leaf);
+        Ok(index)
+    }
+
+    /// Returns the index of the first leaf equal to `value`, e.g. to find the
+    /// witness of a voter's public key. Only the leaves are searched, not the
+    /// internal nodes.
+    pub fn index_of(&self, value: Fp) -> Option<usize> {
+        let leaves = MerkleTree::max_leaves_for_depth(self.depth)
+            .expect("the depth of a built tree is valid");
+        self.nodes[..leaves].iter().position(|leaf| *leaf == value)
+    }
+
+    /// Returns whether a leaf of the tree is `value`.
+    pub fn contains(&self, value: Fp) -> bool {
+        self.index_of(value).is_some()
+    }
+
+    /// Sets the leaf at `index` to `value`, recomputing only the nodes in its
+    /// path to the root.
+    pub fn update(&mut self, index: usize, value: Fp) -> Result<(), MerkleError> {
+        self.check_leaf_index(index)?;
+        self.update_leaf(index, value);
+        Ok(())
+    }
+
+    fn update_leaf(&mut self, mut index: usize, value: Fp) {
+        self.nodes[index] = value;
+
+        let mut base = 0;
+        for n in 0..self.depth - 1 {
+            let level_size = 2usize.pow(self.depth - n - 1);
+            let left = base + (index & !1);
+            let parent = base + level_size + (index >> 1);
+            self.nodes[parent] = self.hasher.hash(self.nodes[left], self.nodes[left + 1]);
+            base += level_size;
+            index >>= 1;
+        }
+    }
+
+    /// Returns the leaves of `other` that differ from the ones of `self`, in
+    /// index order. Both trees must have the same depth.
+    pub fn diff(&self, other: &MerkleTree<H>) -> Result<Vec<LeafDiff>, MerkleError> {
+        if self.depth != other.depth {
+            return Err(MerkleError::DepthMismatch {
+                expected: self.depth,
+                actual: other.depth,
+            });
+        }
+
+        let leaves = 2usize.pow(self.depth - 1);
+        Ok(self.nodes[..leaves]
+            .iter()
+            .zip(other.nodes[..leaves].iter())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(index, (old, new))| LeafDiff {
+                index,
+                old: *old,
+                new: *new,
+            })
+            .collect())
+    }
+
+    /// Returns the node at `index`, in the [`MerkleTree::to_flat`] ordering.
+    pub fn get(&self, index: usize) -> Result<Fp, MerkleError> {
+        self.nodes
+            .get(index)
+            .copied()
+            .ok_or(MerkleError::IndexOutOfRange {
+                index,
+                len: self.nodes.len(),
+            })
+    }
+
+    fn check_leaf_index(&self, index: usize) -> Result<(), MerkleError> {
+        let len = MerkleTree::max_leaves_for_depth(self.depth)
+            .expect("the depth of a built tree is valid");
+        if index >= len {
+            return Err(MerkleError::IndexOutOfRange { index, len });
+        }
+        Ok(())
+    }
+
+    pub fn witness(&self, mut index: usize) -> Result<Vec<(Fp, bool)>, MerkleError> {
+        self.check_leaf_index(index)?;
+
+        let mut base = 0;
+        let mut siblings = Vec::new();
+        for n in 0..self.depth - 1 {
+            let left_right = 1 - (index & 1);
+            siblings.push((
+                self.nodes[base + (index & !1) + left_right],
+                left_right == 1,
+            ));
+            base += 2usize.pow(self.depth - n - 1);
+            index >>= 1;
+        }
+        Ok(siblings)
+    }
+
+    /// Returns a generator of witnesses for many leaves, computing the level
+    /// offsets in the nodes array once.
+    pub fn witness_generator(&self) -> WitnessGenerator<'_, H> {
+        let mut offsets = Vec::with_capacity(self.depth as usize - 1);
+        let mut base = 0;
+        for n in 0..self.depth - 1 {
+            offsets.push(base);
+            base += 2usize.pow(self.depth - n - 1);
+        }
+        WitnessGenerator {
+            tree: self,
+            offsets,
+        }
+    }
+}
+
+// the constructors and the static witness checks use the default Poseidon
+// hasher, the one the circuit proves membership with
+impl MerkleTree {
+    /// Builds the tree consuming the leaves lazily from `iter`, so they don't
+    /// need to be materialized beforehand. Fails if `iter` yields more leaves
+    /// than the `2^(depth-1)` the tree can hold.
+    pub fn from_leaf_iter<I: Iterator<Item = Fp>>(
+        depth: u32,
+        iter: I,
+    ) -> Result<MerkleTree, MerkleError> {
+        if depth == 0 {
+            return Err(MerkleError::DepthTooSmall);
+        }
+        let mut builder = MerkleTreeBuilder::new(depth);
+        builder.extend(iter)?;
+        Ok(builder.build())
+    }
+
+    /// Builds the tree with the `leaves`, filling the unused ones with zeroes.
+    /// Fails if there are more than the `2^(depth-1)` the tree can hold.
+    pub fn from_leaves(depth: u32, leaves: &[Fp]) -> Result<MerkleTree, MerkleError> {
+        Self::from_leaf_iter(depth, leaves.iter().copied())
+    }
+
+    /// Like [`MerkleTree::from_leaves`], but hashing the nodes with `hasher`.
+    pub fn from_leaves_with_hasher<H: MerkleHasher>(
+        depth: u32,
+        leaves: &[Fp],
+        hasher: H,
+    ) -> Result<MerkleTree<H>, MerkleError> {
+        if depth == 0 {
+            return Err(MerkleError::DepthTooSmall);
+        }
+        let mut builder = MerkleTreeBuilder::new(depth);
+        builder.extend(leaves.iter().copied())?;
+        Ok(builder.build_with_hasher(hasher))
+    }
+
+    /// Imports a tree from its flat node array, in the [`MerkleTree::to_flat`]
+    /// ordering. The nodes are not rehashed. Fails with
+    /// [`MerkleError::DepthTooLarge`] if the `2^depth - 1` nodes of the tree
+    /// can't be counted in a `usize`.
+    pub fn from_flat(depth: u32, nodes: Vec<Fp>) -> Result<Self, MerkleError> {
+        if depth == 0 {
+            return Err(MerkleError::DepthTooSmall);
+        }
+        let expected = MerkleTree::max_leaves_for_depth(depth)
+            .and_then(|leaves| leaves.checked_mul(2))
+            .ok_or(MerkleError::DepthTooLarge {
+                max: usize::BITS - 1,
+            })?
+            - 1;
+        if nodes.len() != expected {
+            return Err(MerkleError::InvalidLength {
+                expected,
+                actual: nodes.len(),
+            });
+        }
+        Ok(MerkleTree {
+            depth,
+            nodes,
+            hasher: PoseidonHasher,
+        })
+    }
+
+    /// Restores a tree serialized with [`MerkleTree::to_bytes`]. The nodes are
+    /// not rehashed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() < 4 {
+            return Err(MerkleError::InvalidBytes);
+        }
+        let mut depth = [0; 4];
+        depth.copy_from_slice(&bytes[..4]);
+        let depth = u32::from_le_bytes(depth);
+
+        let chunks = bytes[4..].chunks_exact(32);
+        if !chunks.remainder().is_empty() {
+            return Err(MerkleError::InvalidBytes);
+        }
+        let nodes = chunks
+            .map(|chunk| {
+                let mut repr = [0; 32];
+                repr.copy_from_slice(chunk);
+                Option::from(Fp::from_repr(repr)).ok_or(MerkleError::InvalidBytes)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_flat(depth, nodes)
+    }
+
+    /// Returns the number of leaves of a tree of `depth` levels, `2^(depth-1)`,
+    /// or `None` if `depth` is 0 or the count doesn't fit in a `usize`. See
+    /// [`max_voters_for_lvl`] for the relation with the circuit depth.
+    pub fn max_leaves_for_depth(depth: u32) -> Option<usize> {
+        depth
+            .checked_sub(1)
+            .and_then(|exponent| 2usize.checked_pow(exponent))
+    }
+
+    /// Like [`MerkleTree::witness`], but omitting the siblings of empty subtrees,
+    /// which for a mostly-empty census are most of them.
+    /// Fails with [`MerkleError::DepthTooLarge`] if the tree has more than 65
+    /// levels.
+    pub fn compact_witness(&self, index: usize) -> Result<CompactWitness, MerkleError> {
+        CompactWitness::compress(self.witness(index)?, &empty_hashes(self.depth))
+    }
+
+    /// Checks a compact witness of [`MerkleTree::compact_witness`] or
+    /// [`SparseMerkleTree::compact_witness`]. A malformed witness doesn't
+    /// verify.
+    pub fn verify_compact_witness(value: Fp, witness: &CompactWitness, root: Fp) -> bool {
+        match witness.expand() {
+            Some(siblings) => Self::check_witness(value, siblings, root),
+            None => false,
+        }
+    }
+
+    pub fn check_witness(value: Fp, siblings: Vec<(Fp, bool)>, root: Fp) -> bool {
+        Self::compute_root(value, &siblings) == root
+    }
+
+    /// Returns the root reached by hashing the leaf `value` up with its
+    /// `siblings`, e.g. to compare it with several candidate roots or to report
+    /// the root a witness actually leads to.
+    pub fn compute_root(value: Fp, siblings: &[(Fp, bool)]) -> Fp {
+        let mut hash = value;
+        for (sibling, order) in siblings {
+            hash = if *order {
+                MerkleTreeBuilder::hash(hash, *sibling)
+            } else {
+                MerkleTreeBuilder::hash(*sibling, hash)
+            };
+        }
+        hash
+    }
+}
+
+/// Generates witnesses of a [`MerkleTree`], see [`MerkleTree::witness_generator`].
+pub struct WitnessGenerator<'a, H: MerkleHasher = PoseidonHasher> {
+    tree: &'a MerkleTree<H>,
+    offsets: Vec<usize>,
+}
+
+impl<'a, H: MerkleHasher> WitnessGenerator<'a, H> {
+    /// Returns the same witness as [`MerkleTree::witness`].
+    pub fn get(&self, mut index: usize) -> Result<Vec<(Fp, bool)>, MerkleError> {
+        self.tree.check_leaf_index(index)?;
+
+        Ok(self
+            .offsets
+            .iter()
+            .map(|base| {
+                let left_right = 1 - (index & 1);
+                let sibling = (
+                    self.tree.nodes[base + (index & !1) + left_right],
+                    left_right == 1,
+                );
+                index >>= 1;
+                sibling
+            })
+            .collect())
+    }
+}
+
+/// A Merkle proof in the layout used by most Merkle libraries: the leaf, the
+/// siblings from the leaf level up, and the index bits of the path, where a
+/// set bit means the node at that level is the right child.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenericMerkleProof {
+    pub leaf: Fp,
+    pub siblings: Vec<Fp>,
+    pub path_bits: Vec<bool>,
+}
+
+impl GenericMerkleProof {
+    /// Converts from the [`MerkleTree::witness`] format, where `true` means the
+    /// node is the left child, i.e. `path_bit = !direction`.
+    pub fn from_witness(leaf: Fp, witness: &[(Fp, bool)]) -> Self {
+        GenericMerkleProof {
+            leaf,
+            siblings: witness.iter().map(|(sibling, _)| *sibling).collect(),
+            path_bits: witness.iter().map(|(_, direction)| !direction).collect(),
+        }
+    }
+
+    /// Converts to the [`MerkleTree::witness`] format, `direction = !path_bit`.
+    pub fn to_witness(&self) -> Vec<(Fp, bool)> {
+        self.siblings
+            .iter()
+            .zip(self.path_bits.iter())
+            .map(|(sibling, path_bit)| (*sibling, !path_bit))
+            .collect()
+    }
+
+    /// Converts from the circuit `pri_index` and `pri_siblings` witnesses.
+    /// `pri_index[n]` is set when the node is swapped to the right, so it maps
+    /// to the path bits as is.
+    pub fn from_circuit<const LVL: usize>(
+        leaf: Fp,
+        pri_index: [bool; LVL],
+        pri_siblings: [Fp; LVL],
+    ) -> Self {
+        GenericMerkleProof {
+            leaf,
+            siblings: pri_siblings.to_vec(),
+            path_bits: pri_index.to_vec(),
+        }
+    }
+
+    /// Converts to the circuit `(pri_index, pri_siblings)` witnesses, or `None`
+    /// if the proof doesn't have `LVL` levels.
+    pub fn to_circuit<const LVL: usize>(&self) -> Option<([bool; LVL], [Fp; LVL])> {
+        if self.siblings.len() != LVL || self.path_bits.len() != LVL {
+            return None;
+        }
+        let mut pri_index = [false; LVL];
+        let mut pri_siblings = [Fp::zero(); LVL];
+        pri_index.copy_from_slice(&self.path_bits);
+        pri_siblings.copy_from_slice(&self.siblings);
+        Some((pri_index, pri_siblings))
+    }
+
+    /// Computes the root implied by the proof.
+    pub fn root(&self) -> Fp {
+        let mut hash = self.leaf;
+        for (sibling, path_bit) in self.siblings.iter().zip(self.path_bits.iter()) {
+            hash = if *path_bit {
+                MerkleTreeBuilder::hash(*sibling, hash)
+            } else {
+                MerkleTreeBuilder::hash(hash, *sibling)
+            };
+        }
+        hash
+    }
+}
+
+/// Checks many Merkle paths against one root, computing the Poseidon constants
+/// once instead of on every hash as [`MerkleTree::check_witness`] does.
+pub struct PathValidator {
+    root: Fp,
+    round_constants: Vec<[Fp; 3]>,
+    mds: Mds<Fp, 3>,
+    capacity_element: Fp,
+}
+
+impl PathValidator {
+    pub fn new(root: Fp) -> Self {
+        let (round_constants, mds, _) = P128Pow5T3.constants();
+        PathValidator {
+            root,
+            round_constants,
+            mds,
+            capacity_element: Domain::<Fp, 3, 2>::initial_capacity_element(&ConstantLength::<2>),
+        }
+    }
+
+    fn hash(&self, first: Fp, second: Fp) -> Fp {
+        let mut state = [first, second, self.capacity_element];
+        poseidon::permute_with::<_, P128Pow5T3, 3, 2>(&mut state, &self.mds, &self.round_constants);
+        state[0]
+    }
+
+    /// Checks that `leaf` is in the tree with the validator root, given its
+    /// siblings in the [`MerkleTree::witness`] format.
+    pub fn validate(&self, leaf: Fp, siblings: &[(Fp, bool)]) -> bool {
+        let mut hash = leaf;
+        for (sibling, order) in siblings {
+            hash = if *order {
+                self.hash(hash, *sibling)
+            } else {
+                self.hash(*sibling, hash)
+            };
+        }
+        hash == self.root
+    }
+}
+
+/// Returns the witness of any leaf of an empty tree of `depth` levels: the
+/// [`empty_hashes`] of each level as siblings, with the node on the left. Its
+/// levels from `n` upwards are the canonical padding of the witness of a tree of
+/// depth `n + 1`, see [`pad_witness_to_lvl`].
+pub fn empty_witness(depth: u32) -> Vec<(Fp, bool)> {
+    let mut empty = empty_hashes(depth);
+    empty.pop();
+    empty.into_iter().map(|hash| (hash, true)).collect()
+}
+
+/// Pads the witness of a shallow tree with `empty_hashes` siblings up to
+/// `target_lvl` levels, so that it can be proven in a `FranchiseCircuit` of
+/// that depth without revealing the real tree depth. The padded witness is
+/// against the root of the tree of depth `target_lvl + 1` holding the same
+/// leaves, which is the root to publish for the census. `empty_hashes` is
+/// [`empty_hashes`]`(target_lvl + 1)`.
+pub fn pad_witness_to_lvl(
+    siblings: &[(Fp, bool)],
+    target_lvl: usize,
+    empty_hashes: &[Fp],
+) -> Vec<(Fp, bool)> {
+    assert!(siblings.len() <= target_lvl && target_lvl <= empty_hashes.len());
+
+    let mut padded = siblings.to_vec();
+    // the real tree is the leftmost subtree, next to empty ones
+    padded.extend((siblings.len()..target_lvl).map(|n| (empty_hashes[n], true)));
+    padded
+}
+
+/// A Merkle tree of `depth` levels storing only its non-empty nodes, the rest
+/// being the [`empty_hashes`] of their level. The leaf of a key is at the index
+/// given by the low `depth - 1` bits of the key, so its root is the one of the
+/// dense [`MerkleTree`] with the same leaves, and its witnesses can be proven by
+/// the circuit. Unset leaves are zero, so a key set to zero is absent.
+pub struct SparseMerkleTree {
+    depth: u32,
+    empty: Vec<Fp>,
+    // nodes by level, and key with the bits below the level cleared
+    nodes: HashMap<(u32, [u8; 32]), Fp>,
+}
+
+/// A [`SparseMerkleTree`] proof: `value` is the leaf of `key` for a membership
+/// proof, or `None` for a non-membership proof, where the leaf is empty. The
+/// `siblings` go from the leaf level up, and the key bits give the path, a set
+/// bit meaning the node at that level is the right child, as in
+/// [`GenericMerkleProof`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMerkleProof {
+    pub key: Fp,
+    pub value: Option<Fp>,
+    pub siblings: Vec<Fp>,
+}
+
+fn key_bit(key: &[u8; 32], n: u32) -> bool {
+    key[n as usize / 8] >> (n % 8) & 1 == 1
+}
+
+/// Clears the bits of `key` below `level`, identifying the node of the key path
+/// at that level.
+fn key_prefix(mut key: [u8; 32], level: u32) -> [u8; 32] {
+    for n in 0..level {
+        key[n as usize / 8] &= !(1 << (n % 8));
+    }
+    key
+}
+
+impl SparseMerkleTree {
+    /// Creates an empty tree of `depth` levels. Fails with
+    /// [`MerkleError::DepthTooLarge`] if `depth` is over 256, as keys have 255
+    /// bits.
+    pub fn new(depth: u32) -> Result<Self, MerkleError> {
+        if depth == 0 {
+            return Err(MerkleError::DepthTooSmall);
+        }
+        if depth > 256 {
+            return Err(MerkleError::DepthTooLarge { max: 256 });
+        }
+        Ok(SparseMerkleTree {
+            depth,
+            empty: empty_hashes(depth),
+            nodes: HashMap::new(),
+        })
+    }
+
+    fn node(&self, level: u32, key: [u8; 32]) -> Fp {
+        self.nodes
+            .get(&(level, key_prefix(key, level)))
+            .copied()
+            .unwrap_or(self.empty[level as usize])
+    }
+
+    fn check_key(&self, key: Fp) -> Result<[u8; 32], MerkleError> {
+        let key = key.to_repr();
+        if (self.depth - 1..256).any(|n| key_bit(&key, n)) {
+            return Err(MerkleError::KeyOutOfRange);
+        }
+        Ok(key)
+    }
+
+    /// Sets the leaf of `key` to `value`, recomputing the nodes in its path.
+    pub fn insert(&mut self, key: Fp, value: Fp) -> Result<(), MerkleError> {
+        let key = self.check_key(key)?;
+
+        let mut hash = value;
+        for level in 0..self.depth {
+            self.nodes.insert((level, key_prefix(key, level)), hash);
+            if level == self.depth - 1 {
+                break;
+            }
+            let mut sibling_key = key;
+            sibling_key[level as usize / 8] ^= 1 << (level % 8);
+            let sibling = self.node(level, sibling_key);
+            hash = if key_bit(&key, level) {
+                MerkleTreeBuilder::hash(sibling, hash)
+            } else {
+                MerkleTreeBuilder::hash(hash, sibling)
+            };
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> Fp {
+        self.node(self.depth - 1, [0; 32])
+    }
+
+    /// Returns the witness of [`SparseMerkleTree::proof`] without the siblings
+    /// of empty subtrees, most of them in a sparse tree. Fails with
+    /// [`MerkleError::DepthTooLarge`] if the tree has more than 65 levels.
+    pub fn compact_witness(&self, key: Fp) -> Result<CompactWitness, MerkleError> {
+        CompactWitness::compress(self.proof(key)?.to_witness(), &self.empty)
+    }
+
+    /// Returns the membership proof of `key`, or its non-membership proof if
+    /// its leaf is empty.
+    pub fn proof(&self, key: Fp) -> Result<SparseMerkleProof, MerkleError> {
+        let key_repr = self.check_key(key)?;
+
+        let siblings = (0..self.depth - 1)
+            .map(|level| {
+                let mut sibling_key = key_repr;
+                sibling_key[level as usize / 8] ^= 1 << (level % 8);
+                self.node(level, sibling_key)
+            })
+            .collect();
+        let leaf = self.node(0, key_repr);
+
+        Ok(SparseMerkleProof {
+            key,
+            value: if leaf == Fp::zero() { None } else { Some(leaf) },
+            siblings,
+        })
+    }
+}
+
+impl SparseMerkleProof {
+    /// Returns the witness of the proof in the [`MerkleTree::witness`] format.
+    pub fn to_witness(&self) -> Vec<(Fp, bool)> {
+        let key = self.key.to_repr();
+        self.siblings
+            .iter()
+            .enumerate()
+            .map(|(n, sibling)| (*sibling, !key_bit(&key, n as u32)))
+            .collect()
+    }
+
+    /// Checks the proof against `root`: the leaf of the key is `value`, or is
+    /// empty for a non-membership proof.
+    pub fn verify(&self, root: Fp) -> bool {
+        MerkleTree::check_witness(self.value.unwrap_or_else(Fp::zero), self.to_witness(), root)
+    }
+}
+
+/// How [`import_witness`] handles a witness that doesn't have `LVL` levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImportMode {
+    /// Fail with [`MerkleError::InvalidLength`].
+    #[default]
+    Strict,
+    /// Drop the levels beyond `LVL`, and fill the missing ones with zero
+    /// siblings. The result is only provable against the root of the witness
+    /// if it has at least `LVL` levels.
+    Truncate,
+    /// Pad a shorter witness with the [`empty_hashes`] of the missing levels,
+    /// as [`pad_witness_to_lvl`] does, so it is provable against the root of the
+    /// tree of `LVL` levels holding the same leaves. A longer witness fails.
+    PadEmpty,
+}
+
+/// Converts a [`MerkleTree::witness`] into the circuit `(pri_index,
+/// pri_siblings)` witnesses, handling a length other than `LVL` as `mode`
+/// says.
+#[allow(clippy::type_complexity)]
+pub fn import_witness<const LVL: usize>(
+    witness: &[(Fp, bool)],
+    mode: ImportMode,
+) -> Result<([bool; LVL], [Fp; LVL]), MerkleError> {
+    let invalid_length = MerkleError::InvalidLength {
+        expected: LVL,
+        actual: witness.len(),
+    };
+    let witness = match mode {
+        ImportMode::Strict if witness.len() != LVL => return Err(invalid_length),
+        ImportMode::PadEmpty if witness.len() > LVL => return Err(invalid_length),
+        ImportMode::PadEmpty => pad_witness_to_lvl(witness, LVL, &empty_hashes(LVL as u32 + 1)),
+        _ => witness.iter().take(LVL).copied().collect(),
+    };
+
+    let mut pri_siblings = [Fp::zero(); LVL];
+    let mut pri_index = [false; LVL];
+    for (n, (l, p)) in witness.iter().enumerate() {
+        pri_siblings[n] = *l;
+        pri_index[n] = !p;
+    }
+    Ok((pri_index, pri_siblings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::franchise::FranchiseCircuit;
+    use crate::halo2::dev::MockProver;
+    use crate::utils::{
+        deterministic_census, generate_circuit_inputs, generate_circuit_inputs_with_mode,
+        secret_to_public_key,
+    };
+    use ff::Field;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    #[test]
+    fn simple_mt_test() {
+        let mut tree = MerkleTreeBuilder::new(6);
+        for n in 0..2u64.pow(tree.depth - 1) {
+            tree.insert(Fp::from(n)).unwrap();
+        }
+        let tree = tree.build();
+        tree.print_tree();
+        for n in 0..2usize.pow(tree.depth - 1) {
+            let witness = tree.witness(n).unwrap();
+            assert!(MerkleTree::check_witness(
+                tree.get(n).unwrap(),
+                witness,
+                tree.root()
+            ));
+        }
+    }
+
+    #[test]
+    fn compact_witness_test() {
+        let mut tree = MerkleTreeBuilder::new(10);
+        for n in 0..3u64 {
+            tree.insert(Fp::from(n + 1)).unwrap();
+        }
+        let tree = tree.build();
+        for n in 0..3 {
+            let dense = tree.witness(n).unwrap();
+            let compact = tree.compact_witness(n).unwrap();
+            assert!(compact.siblings.len() < dense.len());
+            assert_eq!(compact.expand(), Some(dense));
+            assert!(MerkleTree::verify_compact_witness(
+                tree.get(n).unwrap(),
+                &compact,
+                tree.root()
+            ));
+            assert!(!MerkleTree::verify_compact_witness(
+                tree.get(n).unwrap() + Fp::one(),
+                &compact,
+                tree.root()
+            ));
+        }
+
+        // malformed witnesses don't verify instead of panicking
+        let compact = tree.compact_witness(0).unwrap();
+        let leaf = tree.get(0).unwrap();
+        let mut missing_sibling = compact.clone();
+        missing_sibling.siblings.pop();
+        let mut extra_sibling = compact.clone();
+        extra_sibling.siblings.push(Fp::one());
+        let mut mask_beyond_levels = compact.clone();
+        mask_beyond_levels.empty_mask |= 1 << 40;
+        let mut too_many_levels = compact;
+        too_many_levels.directions = vec![true; 65];
+        for witness in [
+            missing_sibling,
+            extra_sibling,
+            mask_beyond_levels,
+            too_many_levels,
+        ] {
+            assert_eq!(witness.expand(), None);
+            assert!(!MerkleTree::verify_compact_witness(
+                leaf,
+                &witness,
+                tree.root()
+            ));
+        }
+    }
+
+    #[test]
+    fn sparse_compact_witness_test() {
+        let mut tree = SparseMerkleTree::new(33).unwrap();
+        tree.insert(Fp::from(5), Fp::from(50)).unwrap();
+        tree.insert(Fp::from(1 << 31), Fp::from(60)).unwrap();
+
+        let compact = tree.compact_witness(Fp::from(5)).unwrap();
+        let dense = tree.proof(Fp::from(5)).unwrap().to_witness();
+        assert_eq!(compact.siblings.len(), 1);
+        assert_eq!(compact.expand(), Some(dense));
+        assert!(MerkleTree::verify_compact_witness(
+            Fp::from(50),
+            &compact,
+            tree.root()
+        ));
+
+        // a non-membership witness verifies the empty leaf
+        let compact = tree.compact_witness(Fp::from(6)).unwrap();
+        assert!(MerkleTree::verify_compact_witness(
+            Fp::zero(),
+            &compact,
+            tree.root()
+        ));
+        assert!(!MerkleTree::verify_compact_witness(
+            Fp::from(50),
+            &compact,
+            tree.root()
+        ));
+
+        assert_eq!(
+            SparseMerkleTree::new(66)
+                .unwrap()
+                .compact_witness(Fp::from(5)),
+            Err(MerkleError::DepthTooLarge { max: 65 })
+        );
+    }
+
+    #[test]
+    fn capacity_hint_test() {
+        let mut tree = MerkleTreeBuilder::new(6);
+        let mut hinted = MerkleTreeBuilder::with_capacity_hint(6, 2);
+        for n in 0..20u64 {
+            tree.insert(Fp::from(n)).unwrap();
+            hinted.insert(Fp::from(n)).unwrap();
+        }
+        let (tree, hinted) = (tree.build(), hinted.build());
+        assert_eq!(tree.root(), hinted.root());
+        for n in 0..20 {
+            assert!(MerkleTree::check_witness(
+                hinted.get(n).unwrap(),
+                hinted.witness(n).unwrap(),
+                hinted.root()
+            ));
+        }
+    }
+
+    #[test]
+    fn flat_tree_test() {
+        let (tree, _) = deterministic_census(5, 10, 7);
+        let imported = MerkleTree::from_flat(5, tree.to_flat()).unwrap();
+        assert_eq!(imported.root(), tree.root());
+        for n in 0..16 {
+            assert_eq!(imported.witness(n).unwrap(), tree.witness(n).unwrap());
+        }
+
+        let mut nodes = tree.to_flat();
+        nodes.pop();
+        assert_eq!(
+            MerkleTree::from_flat(5, nodes).err(),
+            Some(MerkleError::InvalidLength {
+                expected: 31,
+                actual: 30
+            })
+        );
+    }
+
+    #[test]
+    fn leaf_iter_test() {
+        let tree = MerkleTree::from_leaf_iter(11, (0..1000u64).map(Fp::from)).unwrap();
+
+        let mut builder = MerkleTreeBuilder::new(11);
+        for n in 0..1000u64 {
+            builder.insert(Fp::from(n)).unwrap();
+        }
+        assert_eq!(tree.root(), builder.build().root());
+
+        assert_eq!(
+            MerkleTree::from_leaf_iter(11, (0..1025u64).map(Fp::from)).err(),
+            Some(MerkleError::Full { capacity: 1024 })
+        );
+    }
+
+    #[test]
+    fn generic_merkle_proof_test() {
+        let (tree, _) = deterministic_census(4, 6, 5);
+        for index in [0, 3, 5] {
+            let leaf = tree.get(index).unwrap();
+            let witness = tree.witness(index).unwrap();
+
+            let proof = GenericMerkleProof::from_witness(leaf, &witness);
+            assert_eq!(proof.root(), tree.root());
+            assert_eq!(proof.to_witness(), witness);
+
+            let (pri_index, pri_siblings) = proof.to_circuit::<3>().unwrap();
+            let (circuit, _) =
+                generate_circuit_inputs::<3>(Fp::one(), [Fp::one(); 2], Fp::one(), &witness);
+            assert_eq!(circuit.pri_index, Some(pri_index));
+            assert_eq!(circuit.pri_siblings, Some(pri_siblings));
+
+            let proof = GenericMerkleProof::from_circuit(leaf, pri_index, pri_siblings);
+            assert_eq!(proof.root(), tree.root());
+            assert_eq!(proof.to_witness(), witness);
+        }
+        assert!(GenericMerkleProof::from_witness(Fp::zero(), &[])
+            .to_circuit::<3>()
+            .is_none());
+    }
+
+    #[test]
+    fn diff_test() {
+        let leaves: Vec<Fp> = (0..6u64).map(Fp::from).collect();
+        let tree = MerkleTree::from_leaf_iter(4, leaves.iter().cloned()).unwrap();
+        assert_eq!(tree.diff(&tree), Ok(vec![]));
+
+        let mut updated = leaves.clone();
+        updated[1] = Fp::from(10);
+        updated.push(Fp::from(11));
+        let updated = MerkleTree::from_leaf_iter(4, updated.into_iter()).unwrap();
+        assert_eq!(
+            tree.diff(&updated),
+            Ok(vec![
+                LeafDiff {
+                    index: 1,
+                    old: Fp::from(1),
+                    new: Fp::from(10)
+                },
+                LeafDiff {
+                    index: 6,
+                    old: Fp::zero(),
+                    new: Fp::from(11)
+                },
+            ])
+        );
+
+        let deeper = MerkleTree::from_leaf_iter(5, leaves.into_iter()).unwrap();
+        assert_eq!(
+            tree.diff(&deeper),
+            Err(MerkleError::DepthMismatch {
+                expected: 4,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn path_validator_test() {
+        let (tree, _) = deterministic_census(5, 10, 11);
+        let validator = PathValidator::new(tree.root());
+
+        // members only, the empty leaves are all equal
+        for index in 0..10 {
+            let witness = tree.witness(index).unwrap();
+            assert!(validator.validate(tree.get(index).unwrap(), &witness));
+            assert!(!validator.validate(tree.get(index).unwrap() + Fp::one(), &witness));
+            assert!(!validator.validate(tree.get(index ^ 1).unwrap(), &witness));
+        }
+    }
+
+    #[test]
+    fn rotate_key_test() {
+        let (mut tree, secret_keys) = deterministic_census(4, 5, 13);
+        let old_leaf = secret_to_public_key(secret_keys[3]);
+        let old_witness = tree.witness(3).unwrap();
+        let old_root = tree.root();
+
+        let new_leaf = secret_to_public_key(Fp::from(1234));
+        assert_eq!(tree.rotate_key(old_leaf, new_leaf), Ok(3));
+        assert_ne!(tree.root(), old_root);
+
+        let mut rebuilt = MerkleTreeBuilder::new(4);
+        for (n, secret_key) in secret_keys.iter().enumerate() {
+            rebuilt
+                .insert(if n == 3 {
+                    new_leaf
+                } else {
+                    secret_to_public_key(*secret_key)
+                })
+                .unwrap();
+        }
+        assert_eq!(tree.to_flat(), rebuilt.build().to_flat());
+
+        assert!(MerkleTree::check_witness(
+            new_leaf,
+            tree.witness(3).unwrap(),
+            tree.root()
+        ));
+        assert!(!MerkleTree::check_witness(
+            old_leaf,
+            old_witness,
+            tree.root()
+        ));
+
+        assert_eq!(
+            tree.rotate_key(old_leaf, new_leaf),
+            Err(MerkleError::LeafNotFound)
+        );
+    }
+
+    #[test]
+    fn max_leaves_for_depth_test() {
+        assert_eq!(MerkleTree::max_leaves_for_depth(0), None);
+        assert_eq!(MerkleTree::max_leaves_for_depth(1), Some(1));
+        assert_eq!(MerkleTree::max_leaves_for_depth(4), Some(8));
+        assert_eq!(
+            MerkleTree::max_leaves_for_depth(usize::BITS),
+            Some(1 << (usize::BITS - 1))
+        );
+        assert_eq!(MerkleTree::max_leaves_for_depth(usize::BITS + 1), None);
+        assert_eq!(MerkleTree::max_leaves_for_depth(u32::MAX), None);
+
+        let mut builder = MerkleTreeBuilder::with_capacity_hint(0, 1);
+        assert_eq!(builder.insert(Fp::one()), Err(MerkleError::DepthTooSmall));
+        let mut builder = MerkleTreeBuilder::with_capacity_hint(usize::BITS + 1, 1);
+        assert_eq!(
+            builder.insert(Fp::one()),
+            Err(MerkleError::DepthTooLarge { max: usize::BITS })
+        );
+    }
+
+    #[test]
+    fn witness_generator_test() {
+        let tree = MerkleTree::from_leaf_iter(8, (0..100u64).map(Fp::from)).unwrap();
+        let generator = tree.witness_generator();
+        for index in 0..MerkleTree::max_leaves_for_depth(8).unwrap() {
+            assert_eq!(generator.get(index).unwrap(), tree.witness(index).unwrap());
+        }
+    }
+
+    #[test]
+    fn merkle_hasher_test() {
+        struct SumHasher;
+        impl MerkleHasher for SumHasher {
+            fn hash(&self, left: Fp, right: Fp) -> Fp {
+                left + right
+            }
+        }
+
+        let leaves: Vec<Fp> = (1..=4u64).map(Fp::from).collect();
+        let mut tree = MerkleTree::from_leaves_with_hasher(3, &leaves, SumHasher).unwrap();
+        assert_eq!(tree.root(), Fp::from(10));
+
+        tree.update(0, Fp::from(11)).unwrap();
+        assert_eq!(tree.root(), Fp::from(20));
+        assert_eq!(
+            tree.witness(0).unwrap(),
+            vec![(Fp::from(2), true), (Fp::from(7), true)]
+        );
+
+        let poseidon = MerkleTree::from_leaves(3, &leaves).unwrap();
+        let default = MerkleTree::from_leaves_with_hasher(3, &leaves, PoseidonHasher).unwrap();
+        assert_eq!(poseidon.root(), default.root());
+    }
+
+    #[test]
+    fn insert_full_test() {
+        let mut builder = MerkleTreeBuilder::new(3);
+        for n in 0..4u64 {
+            assert_eq!(builder.insert(Fp::from(n)), Ok(n as usize));
+        }
+        assert_eq!(
+            builder.insert(Fp::from(4)),
+            Err(MerkleError::Full { capacity: 4 })
+        );
+    }
+
+    #[test]
+    fn pad_witness_test() {
+        let (tree, secret_keys) = deterministic_census(5, 12, 17);
+        let padded_tree = MerkleTree::from_leaf_iter(
+            11,
+            secret_keys
+                .iter()
+                .map(|secret_key| secret_to_public_key(*secret_key)),
+        )
+        .unwrap();
+
+        let witness = pad_witness_to_lvl(&tree.witness(9).unwrap(), 10, &empty_hashes(11));
+        assert_eq!(witness, padded_tree.witness(9).unwrap());
+
+        let vote_hash = Fp::from(1);
+        let (circuit, nullifier) = generate_circuit_inputs::<10>(
+            secret_keys[9],
+            [Fp::from(6), Fp::from(7)],
+            vote_hash,
+            &witness,
+        );
+        let prover = MockProver::run(
+            10,
+            &circuit,
+            vec![vec![padded_tree.root(), nullifier, vote_hash]],
+        )
+        .expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+    }
+
+    #[test]
+    fn empty_witness_test() {
+        let empty_tree = MerkleTree::from_leaves(6, &[]).unwrap();
+        assert_eq!(empty_witness(6), empty_tree.witness(0).unwrap());
+        assert!(MerkleTree::check_witness(
+            Fp::zero(),
+            empty_witness(6),
+            empty_tree.root()
+        ));
+
+        let (tree, secret_keys) = deterministic_census(5, 12, 17);
+        let padded_tree = MerkleTree::from_leaf_iter(
+            11,
+            secret_keys
+                .iter()
+                .map(|secret_key| secret_to_public_key(*secret_key)),
+        )
+        .unwrap();
+
+        let mut witness = tree.witness(9).unwrap();
+        witness.extend_from_slice(&empty_witness(11)[4..]);
+        assert!(MerkleTree::check_witness(
+            tree.get(9).unwrap(),
+            witness.clone(),
+            padded_tree.root()
+        ));
+        assert_eq!(
+            witness,
+            pad_witness_to_lvl(&tree.witness(9).unwrap(), 10, &empty_hashes(11))
+        );
+    }
+
+    #[test]
+    fn sparse_merkle_tree_test() {
+        let mut tree = SparseMerkleTree::new(6).unwrap();
+        assert_eq!(tree.root(), *empty_hashes(6).last().unwrap());
+
+        let mut leaves = vec![Fp::zero(); 32];
+        for (key, value) in [(3u64, 30u64), (17, 170), (30, 300)].iter() {
+            tree.insert(Fp::from(*key), Fp::from(*value)).unwrap();
+            leaves[*key as usize] = Fp::from(*value);
+        }
+        let dense = MerkleTree::from_leaves(6, &leaves).unwrap();
+        assert_eq!(tree.root(), dense.root());
+
+        let proof = tree.proof(Fp::from(17)).unwrap();
+        assert_eq!(proof.value, Some(Fp::from(170)));
+        assert_eq!(proof.to_witness(), dense.witness(17).unwrap());
+        assert!(proof.verify(tree.root()));
+
+        let proof = tree.proof(Fp::from(16)).unwrap();
+        assert_eq!(proof.value, None);
+        assert!(proof.verify(tree.root()));
+
+        // an exclusion proof of a member doesn't verify
+        let forged = SparseMerkleProof {
+            value: None,
+            ..tree.proof(Fp::from(3)).unwrap()
+        };
+        assert!(!forged.verify(tree.root()));
+
+        assert_eq!(
+            tree.insert(Fp::from(32), Fp::one()),
+            Err(MerkleError::KeyOutOfRange)
+        );
+        assert_eq!(
+            tree.proof(-Fp::one()).err(),
+            Some(MerkleError::KeyOutOfRange)
+        );
+
+        assert!(SparseMerkleTree::new(256).is_ok());
+        assert_eq!(
+            SparseMerkleTree::new(257).err(),
+            Some(MerkleError::DepthTooLarge { max: 256 })
+        );
+        assert_eq!(
+            SparseMerkleTree::new(0).err(),
+            Some(MerkleError::DepthTooSmall)
+        );
+    }
+
+    #[test]
+    fn compute_root_test() {
+        let tree = MerkleTree::from_leaf_iter(5, (0..10u64).map(Fp::from)).unwrap();
+        let witness = tree.witness(7).unwrap();
+        assert_eq!(MerkleTree::compute_root(Fp::from(7), &witness), tree.root());
+        assert_ne!(MerkleTree::compute_root(Fp::from(8), &witness), tree.root());
+    }
+
+    #[test]
+    fn import_mode_test() {
+        let (tree, secret_keys) = deterministic_census(5, 12, 17);
+        let padded_tree = MerkleTree::from_leaf_iter(
+            7,
+            secret_keys
+                .iter()
+                .map(|secret_key| secret_to_public_key(*secret_key)),
+        )
+        .unwrap();
+        let witness = tree.witness(9).unwrap();
+        let circuit_inputs = |mode| {
+            generate_circuit_inputs_with_mode::<6>(
+                secret_keys[9],
+                [Fp::from(6), Fp::from(7)],
+                Fp::from(1),
+                &witness,
+                mode,
+            )
+        };
+        let verifies = |circuit: &FranchiseCircuit<6>, nullifier| {
+            MockProver::run(
+                9,
+                circuit,
+                vec![vec![padded_tree.root(), nullifier, Fp::from(1)]],
+            )
+            .expect("cannot run mock")
+            .verify()
+            .is_ok()
+        };
+
+        assert_eq!(ImportMode::default(), ImportMode::Strict);
+        assert_eq!(
+            circuit_inputs(ImportMode::Strict).err(),
+            Some(MerkleError::InvalidLength {
+                expected: 6,
+                actual: 4
+            })
+        );
+
+        let (circuit, nullifier) = circuit_inputs(ImportMode::Truncate).unwrap();
+        assert_eq!(circuit.pri_siblings.unwrap()[4..], [Fp::zero(); 2]);
+        assert!(!verifies(&circuit, nullifier));
+
+        let (circuit, nullifier) = circuit_inputs(ImportMode::PadEmpty).unwrap();
+        assert!(verifies(&circuit, nullifier));
+
+        assert_eq!(
+            import_witness::<2>(&witness, ImportMode::PadEmpty).err(),
+            Some(MerkleError::InvalidLength {
+                expected: 2,
+                actual: 4
+            })
+        );
+        let (_, siblings) = import_witness::<2>(&witness, ImportMode::Truncate).unwrap();
+        assert_eq!(siblings, [witness[0].0, witness[1].0]);
+    }
+
+    #[test]
+    fn merkle_tree_bytes_test() {
+        let tree = MerkleTree::from_leaf_iter(5, (0..10u64).map(Fp::from)).unwrap();
+        let bytes = tree.to_bytes();
+        assert_eq!(bytes.len(), 4 + 32 * 31);
+
+        let restored = MerkleTree::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.to_flat(), tree.to_flat());
+        assert_eq!(restored.root(), tree.root());
+
+        assert_eq!(
+            MerkleTree::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+        assert_eq!(
+            MerkleTree::from_bytes(&bytes[..bytes.len() - 32]).err(),
+            Some(MerkleError::InvalidLength {
+                expected: 31,
+                actual: 30
+            })
+        );
+        assert_eq!(
+            MerkleTree::from_bytes(&bytes[..2]).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+
+        let mut non_canonical = bytes;
+        non_canonical[4..36].copy_from_slice(&[0xff; 32]);
+        assert_eq!(
+            MerkleTree::from_bytes(&non_canonical).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+
+        // headers of trees too deep to hold, with no nodes following
+        for depth in [usize::BITS, usize::BITS + 1, u32::MAX] {
+            assert_eq!(
+                MerkleTree::from_bytes(&depth.to_le_bytes()).err(),
+                Some(MerkleError::DepthTooLarge {
+                    max: usize::BITS - 1
+                })
+            );
+        }
+        assert_eq!(
+            MerkleTree::from_bytes(&(usize::BITS - 1).to_le_bytes()).err(),
+            Some(MerkleError::InvalidLength {
+                expected: usize::MAX >> 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn index_of_test() {
+        let tree = MerkleTree::from_leaf_iter(4, (10..15u64).map(Fp::from)).unwrap();
+        assert_eq!(tree.index_of(Fp::from(12)), Some(2));
+        assert!(tree.contains(Fp::from(14)));
+        assert!(!tree.contains(Fp::from(15)));
+        // internal nodes are not leaves
+        assert_eq!(tree.index_of(tree.root()), None);
+        assert_eq!(tree.index_of(tree.get(8).unwrap()), None);
+    }
+
+    #[test]
+    fn merkle_error_test() {
+        let tree = MerkleTree::from_leaf_iter(3, (0..3u64).map(Fp::from)).unwrap();
+        assert_eq!(
+            tree.witness(4),
+            Err(MerkleError::IndexOutOfRange { index: 4, len: 4 })
+        );
+        assert_eq!(
+            tree.witness_generator().get(4),
+            Err(MerkleError::IndexOutOfRange { index: 4, len: 4 })
+        );
+        assert_eq!(
+            tree.compact_witness(4),
+            Err(MerkleError::IndexOutOfRange { index: 4, len: 4 })
+        );
+        assert_eq!(tree.get(6), Ok(tree.root()));
+        assert_eq!(
+            tree.get(7),
+            Err(MerkleError::IndexOutOfRange { index: 7, len: 7 })
+        );
+
+        assert_eq!(
+            MerkleTree::from_leaf_iter(0, std::iter::empty()).err(),
+            Some(MerkleError::DepthTooSmall)
+        );
+        assert_eq!(
+            MerkleTree::from_flat(0, vec![]).err(),
+            Some(MerkleError::DepthTooSmall)
+        );
+    }
+
+    #[test]
+    fn update_test() {
+        let mut rng = ChaCha20Rng::seed_from_u64(19);
+        let mut leaves: Vec<Fp> = (0..32u64).map(Fp::from).collect();
+        let mut tree = MerkleTree::from_leaf_iter(6, leaves.iter().cloned()).unwrap();
+
+        for n in 0..20 {
+            let index = (n * 7) % 32;
+            let value = Fp::random(&mut rng);
+            tree.update(index, value).unwrap();
+            leaves[index] = value;
+
+            let rebuilt = MerkleTree::from_leaf_iter(6, leaves.iter().cloned()).unwrap();
+            assert_eq!(tree.to_flat(), rebuilt.to_flat());
+        }
+
+        assert_eq!(
+            tree.update(32, Fp::one()),
+            Err(MerkleError::IndexOutOfRange { index: 32, len: 32 })
+        );
+    }
+
+    #[test]
+    fn from_leaves_test() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::from_leaves(6, &leaves).unwrap();
+
+        let mut builder = MerkleTreeBuilder::new(6);
+        for leaf in &leaves {
+            builder.insert(*leaf).unwrap();
+        }
+        assert_eq!(tree.to_flat(), builder.build().to_flat());
+
+        let mut builder = MerkleTreeBuilder::new(6);
+        builder.extend(leaves[..10].iter().copied()).unwrap();
+        builder.extend(leaves[10..].iter().copied()).unwrap();
+        assert_eq!(tree.to_flat(), builder.build().to_flat());
+
+        assert_eq!(
+            MerkleTree::from_leaves(4, &leaves).err(),
+            Some(MerkleError::Full { capacity: 8 })
+        );
+    }
+}
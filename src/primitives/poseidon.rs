@@ -5,7 +5,7 @@ use std::fmt;
 use std::iter;
 use std::marker::PhantomData;
 
-use crate::halo2::arithmetic::FieldExt;
+use crate::halo2::{arithmetic::FieldExt, pasta::Fp};
 
 pub(crate) mod fp;
 pub(crate) mod fq;
@@ -75,8 +75,8 @@ pub trait Spec<F: FieldExt, const T: usize, const RATE: usize> {
     }
 }
 
-/// Runs the Poseidon permutation on the given state.
-pub(crate) fn permute<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
+/// Runs the Poseidon permutation on the given state, with the given constants.
+pub(crate) fn permute_with<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
     state: &mut State<F, T>,
     mds: &Mds<F, T>,
     round_constants: &[[F; T]],
@@ -123,6 +123,17 @@ pub(crate) fn permute<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RA
         });
 }
 
+/// Applies the `P128Pow5T3` permutation once to a state over the Pallas base
+/// field, e.g. to compare intermediate states against another implementation.
+///
+/// This is a low-level primitive for debugging: to hash, use [`Hash`], which also
+/// sets the capacity element and pads the input.
+pub fn permute(mut state: [Fp; 3]) -> [Fp; 3] {
+    let (round_constants, mds, _) = P128Pow5T3.constants();
+    permute_with::<_, P128Pow5T3, 3, 2>(&mut state, &mds, &round_constants);
+    state
+}
+
 fn poseidon_duplex<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
     state: &mut State<F, T>,
     input: &SpongeState<F, RATE>,
@@ -132,7 +143,7 @@ fn poseidon_duplex<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE:
 ) -> SpongeState<F, RATE> {
     pad_and_add(state, input);
 
-    permute::<F, S, T, RATE>(state, mds_matrix, round_constants);
+    permute_with::<F, S, T, RATE>(state, mds_matrix, round_constants);
 
     let mut output = [None; RATE];
     for (word, value) in output.iter_mut().zip(state.iter()) {
@@ -273,10 +284,12 @@ impl<F: FieldExt, const T: usize, const RATE: usize, const L: usize> Domain<F, T
 
     fn padding(&self) -> SpongeState<F, RATE> {
         // For constant-input-length hashing, padding consists of the field elements being
-        // zero.
+        // zero, filling the last block when `L` isn't a multiple of the rate.
         let mut padding = [None; RATE];
-        for word in padding.iter_mut().skip(L) {
-            *word = Some(F::zero());
+        if !L.is_multiple_of(RATE) {
+            for word in padding.iter_mut().skip(L % RATE) {
+                *word = Some(F::zero());
+            }
         }
         padding
     }
@@ -296,6 +309,39 @@ impl<F: FieldExt, const T: usize, const RATE: usize, const L: usize> Domain<F, T
     }
 }
 
+/// A Poseidon hash function used with variable input length.
+///
+/// Domain specified in section 4.2 of https://eprint.iacr.org/2019/458.pdf: the
+/// message is padded with a single `1` followed by zeros up to a multiple of
+/// the rate. The paper's capacity element, $2^64$ for an output length of 1,
+/// equals the one of [`ConstantLength<1>`], so the empty message would hash as
+/// `[1]` does there; the capacity element is $2^128 + 2^64$ instead, above the
+/// $L \cdot 2^64$ of any [`ConstantLength`].
+#[derive(Clone, Copy, Debug)]
+pub struct VariableLength;
+
+impl<F: FieldExt, const T: usize, const RATE: usize> Domain<F, T, RATE> for VariableLength {
+    fn initial_capacity_element(&self) -> F {
+        let tag = F::from_u128(1 << 64);
+        tag.square() + tag
+    }
+
+    fn padding(&self) -> SpongeState<F, RATE> {
+        // The padding is absorbed with the message, see `Hash::hash`.
+        [None; RATE]
+    }
+
+    fn pad_and_add(&self) -> Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)> {
+        Box::new(|state, input| {
+            for (word, value) in state.iter_mut().zip(input.iter()) {
+                if let Some(value) = value {
+                    *word += value;
+                }
+            }
+        })
+    }
+}
+
 /// A Poseidon hash function, built around a duplex sponge.
 pub struct Hash<
     F: FieldExt,
@@ -360,12 +406,31 @@ impl<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize, const
     }
 }
 
+impl<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
+    Hash<F, S, VariableLength, T, RATE>
+{
+    /// Hashes the given input, of any length.
+    pub fn hash(mut self, message: &[F]) -> F {
+        for value in message {
+            self.duplex.absorb(*value);
+        }
+        self.duplex.absorb(F::one());
+        for _ in 0..(RATE - (message.len() + 1) % RATE) % RATE {
+            self.duplex.absorb(F::zero());
+        }
+        self.duplex.squeeze()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::halo2::arithmetic::FieldExt;
     use pasta_curves::pallas;
 
-    use super::{permute, ConstantLength, Hash, P128Pow5T3 as OrchardNullifier, Spec};
+    use super::{
+        permute, permute_with, ConstantLength, Hash, P128Pow5T3 as OrchardNullifier, Spec,
+        VariableLength,
+    };
 
     #[test]
     fn orchard_spec_equivalence() {
@@ -379,7 +444,102 @@ mod tests {
         // The result should be equivalent to just directly applying the permutation and
         // taking the first state element as the output.
         let mut state = [message[0], message[1], pallas::Base::from_u128(2 << 64)];
-        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        permute_with::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
         assert_eq!(state[0], result);
     }
+
+    #[test]
+    fn variable_length_padding() {
+        let (round_constants, mds, _) = OrchardNullifier.constants();
+        let a = pallas::Base::from_u64(6);
+        let b = pallas::Base::from_u64(42);
+        let one = pallas::Base::one();
+        let zero = pallas::Base::zero();
+        let capacity = pallas::Base::from_u128(1 << 64).square() + pallas::Base::from_u128(1 << 64);
+
+        // [a, b] is padded to [a, b, 1, 0], absorbed in two blocks.
+        let mut state = [a, b, capacity];
+        permute_with::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        state[0] += one;
+        permute_with::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(
+            state[0],
+            Hash::init(OrchardNullifier, VariableLength).hash(&[a, b])
+        );
+
+        // [a] is padded to [a, 1].
+        let mut state = [a, one, capacity];
+        permute_with::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(
+            state[0],
+            Hash::init(OrchardNullifier, VariableLength).hash(&[a])
+        );
+
+        // The empty message is padded to [1, 0].
+        let mut state = [one, zero, capacity];
+        permute_with::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(
+            state[0],
+            Hash::init(OrchardNullifier, VariableLength).hash(&[])
+        );
+
+        // which doesn't collide with the constant-length hash of [1]
+        assert_ne!(
+            state[0],
+            Hash::init(OrchardNullifier, ConstantLength).hash([one])
+        );
+    }
+
+    #[test]
+    fn constant_length_fp() {
+        use crate::halo2::pasta::Fp;
+        use ff::PrimeField;
+
+        for tv in super::test_vectors::fp::hash() {
+            let message = tv.input.map(|word| Fp::from_repr(word).unwrap());
+            let result = Hash::init(OrchardNullifier, ConstantLength).hash(message);
+            assert_eq!(result.to_repr(), tv.output);
+        }
+
+        // lengths that are not a multiple of the rate, where the last block is
+        // zero-padded: the hashes of [1] and [1, 2, 3], computed with a
+        // standalone Python sponge matching the vectors above
+        let expected = |hex: &str| {
+            let mut repr = [0u8; 32];
+            for (n, byte) in repr.iter_mut().rev().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * n..2 * n + 2], 16).unwrap();
+            }
+            Fp::from_repr(repr).unwrap()
+        };
+        assert_eq!(
+            Hash::init(OrchardNullifier, ConstantLength).hash([Fp::one()]),
+            expected("3144084dcf58715fa3e6a842323b9420223ffa0ac44947fa97eed16ee053b61d")
+        );
+        assert_eq!(
+            Hash::init(OrchardNullifier, ConstantLength).hash([
+                Fp::one(),
+                Fp::from(2),
+                Fp::from(3)
+            ]),
+            expected("18ab42c61eea3e9e5f26229840c68c648b7818c6acd498365082aabe14e1fcea")
+        );
+    }
+
+    #[test]
+    fn permute_fp() {
+        use crate::halo2::pasta::Fp;
+        use ff::PrimeField;
+
+        for tv in super::test_vectors::fp::permute() {
+            let state = tv.initial_state.map(|word| Fp::from_repr(word).unwrap());
+            assert_eq!(permute(state).map(|word| word.to_repr()), tv.final_state);
+        }
+
+        let message = [Fp::from(6), Fp::from(42)];
+        let state = permute([message[0], message[1], Fp::from_u128(2 << 64)]);
+        assert_eq!(
+            state[0],
+            Hash::init(OrchardNullifier, ConstantLength).hash(message)
+        );
+    }
 }
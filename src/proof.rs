@@ -0,0 +1,88 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::encoding::ParseError;
+use crate::transcript::SealedProof;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The bytes of a proof, as created by
+/// [`FranchiseCircuit::prove`](crate::franchise::FranchiseCircuit::prove).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Proof(Vec<u8>);
+
+impl Proof {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Proof(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Seals the proof with its length and checksum, e.g. to send it over the
+    /// network, see [`SealedProof`].
+    pub fn seal(&self) -> SealedProof {
+        SealedProof::seal(self)
+    }
+}
+
+/// Formats the proof bytes as hex.
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the hex proof bytes written by `Display`, with or without a `0x`
+/// prefix.
+impl FromStr for Proof {
+    type Err = ParseError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let digits = hex.strip_prefix("0x").unwrap_or(hex).as_bytes();
+        if !digits.len().is_multiple_of(2) {
+            return Err(ParseError::InvalidHex);
+        }
+        digits
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or(ParseError::InvalidHex)
+            })
+            .collect::<Result<_, _>>()
+            .map(Proof)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_proof() {
+        let proof = Proof::from_bytes(vec![0x00, 0x0f, 0xa0, 0xff]);
+        assert_eq!(proof.len(), 4);
+        assert_eq!(proof.as_bytes(), &[0x00, 0x0f, 0xa0, 0xff]);
+        assert_eq!(proof.to_string(), "000fa0ff");
+        assert_eq!("000fa0ff".parse(), Ok(proof.clone()));
+        assert_eq!("0x000FA0FF".parse(), Ok(proof));
+        assert_eq!("000fa0f".parse::<Proof>(), Err(ParseError::InvalidHex));
+        assert_eq!("000fa0fg".parse::<Proof>(), Err(ParseError::InvalidHex));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::halo2::pasta::Fp;
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::primitives::poseidon::{self, ConstantLength, P128Pow5T3};
+use crate::utils::ValidationError;
+
+/// The `[root, nullifier, vote_hash]` public inputs of a
+/// [`FranchiseCircuit`](crate::franchise::FranchiseCircuit) in its default
+/// layout. The circuit modes exposing more values append them after these, see
+/// [`FranchiseCircuit::instance_values`](crate::franchise::FranchiseCircuit::instance_values).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PublicInputs {
+    pub census_root: Fp,
+    pub nullifier: Fp,
+    pub vote_hash: Fp,
+}
+
+impl PublicInputs {
+    /// Returns the public inputs in the order the circuit exposes them, as
+    /// taken by [`FranchiseCircuit::prove`](crate::franchise::FranchiseCircuit::prove)
+    /// and `verify`.
+    pub fn to_vec(&self) -> Vec<Fp> {
+        vec![self.census_root, self.nullifier, self.vote_hash]
+    }
+
+    /// Reads the public inputs from the circuit order, failing unless there
+    /// are exactly three.
+    pub fn from_vec(public: &[Fp]) -> Result<Self, ValidationError> {
+        match public {
+            [census_root, nullifier, vote_hash] => Ok(PublicInputs {
+                census_root: *census_root,
+                nullifier: *nullifier,
+                vote_hash: *vote_hash,
+            }),
+            _ => Err(ValidationError::InvalidLength {
+                expected: 3,
+                actual: public.len(),
+            }),
+        }
+    }
+
+    /// Computes the single public input of a circuit with `expose_commitment`
+    /// set, `Poseidon(root, Poseidon(nullifier, vote_hash))`.
+    pub fn commitment(&self) -> Fp {
+        let nullifier_vote_hash = poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>)
+            .hash([self.nullifier, self.vote_hash]);
+        poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>)
+            .hash([self.census_root, nullifier_vote_hash])
+    }
+
+    /// Compares the public inputs in constant time, so that the time taken
+    /// doesn't tell which of them differ.
+    pub fn ct_eq(&self, other: &PublicInputs) -> Choice {
+        self.census_root.ct_eq(&other.census_root)
+            & self.nullifier.ct_eq(&other.nullifier)
+            & self.vote_hash.ct_eq(&other.vote_hash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::poseidon_hash2;
+
+    #[test]
+    fn test_public_inputs() {
+        let public = PublicInputs {
+            census_root: Fp::from(1),
+            nullifier: Fp::from(2),
+            vote_hash: Fp::from(3),
+        };
+        assert_eq!(PublicInputs::from_vec(&public.to_vec()), Ok(public));
+        assert_eq!(
+            PublicInputs::from_vec(&public.to_vec()[..2]),
+            Err(ValidationError::InvalidLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        assert!(bool::from(public.ct_eq(&public)));
+        let other = PublicInputs {
+            nullifier: Fp::from(4),
+            ..public
+        };
+        assert!(!bool::from(public.ct_eq(&other)));
+
+        assert_eq!(
+            public.commitment(),
+            poseidon_hash2(Fp::from(1), poseidon_hash2(Fp::from(2), Fp::from(3)))
+        );
+        assert_ne!(public.commitment(), other.commitment());
+    }
+}
@@ -0,0 +1,79 @@
+//! `serde(with = ...)` helpers for the witness types without serde support:
+//! field elements are (de)serialized as their canonical little-endian
+//! `to_repr` bytes, and const generic arrays as sequences of their length.
+
+use std::convert::TryInto;
+
+use crate::halo2::pasta::Fp;
+use ff::PrimeField;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+fn to_fp<E: Error>(bytes: [u8; 32]) -> Result<Fp, E> {
+    Option::from(Fp::from_repr(bytes)).ok_or_else(|| E::custom("non-canonical field element"))
+}
+
+fn to_array<T, E: Error, const N: usize>(values: Vec<T>) -> Result<[T; N], E> {
+    let len = values.len();
+    values
+        .try_into()
+        .map_err(|_| E::invalid_length(len, &format!("{} values", N).as_str()))
+}
+
+pub mod fp_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<Fp>, serializer: S) -> Result<S::Ok, S::Error> {
+        value.map(|value| value.to_repr()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Fp>, D::Error> {
+        Option::<[u8; 32]>::deserialize(deserializer)?
+            .map(to_fp)
+            .transpose()
+    }
+}
+
+pub mod fp_array_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        value: &Option<[Fp; N]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.to_repr())
+                    .collect::<Vec<_>>()
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<Option<[Fp; N]>, D::Error> {
+        Option::<Vec<[u8; 32]>>::deserialize(deserializer)?
+            .map(|values| to_array(values.into_iter().map(to_fp).collect::<Result<_, _>>()?))
+            .transpose()
+    }
+}
+
+pub mod bool_array_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        value: &Option<[bool; N]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(|values| values.to_vec()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<Option<[bool; N]>, D::Error> {
+        Option::<Vec<bool>>::deserialize(deserializer)?
+            .map(to_array)
+            .transpose()
+    }
+}
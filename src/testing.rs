@@ -0,0 +1,156 @@
+//! MockProver helpers to check circuit inputs in unit tests, available with the
+//! `test-utils` feature.
+
+use crate::franchise::FranchiseCircuit;
+use crate::halo2::{
+    dev::{MockProver, VerifyFailure},
+    pasta::Fp,
+    plonk::Error,
+};
+#[cfg(not(feature = "verify-only"))]
+use crate::halo2::{
+    pasta::EqAffine,
+    plonk::{keygen_pk, keygen_vk, Circuit},
+    poly::commitment::Params,
+};
+
+/// Why [`check_satisfied`] failed.
+#[derive(Debug)]
+pub enum MockError {
+    /// The MockProver couldn't synthesize the circuit, e.g. if `k` is too
+    /// small or a witness is missing.
+    Run(Error),
+    /// The constraints that aren't satisfied.
+    Unsatisfied(Vec<VerifyFailure>),
+}
+
+/// Runs the MockProver with `2^k` rows on the circuit and its `public` inputs,
+/// returning the constraints that aren't satisfied, if any.
+pub fn check_satisfied<const LVL: usize>(
+    k: u32,
+    circuit: &FranchiseCircuit<LVL>,
+    public: &[Fp],
+) -> Result<(), MockError> {
+    MockProver::run(k, circuit, vec![public.to_vec()])
+        .map_err(MockError::Run)?
+        .verify()
+        .map_err(MockError::Unsatisfied)
+}
+
+/// Asserts that the circuit is satisfied with its `public` inputs and that it
+/// isn't anymore when any one of them is changed.
+pub fn assert_valid<const LVL: usize>(k: u32, circuit: &FranchiseCircuit<LVL>, public: &[Fp]) {
+    if let Err(err) = check_satisfied(k, circuit, public) {
+        panic!("the circuit is not satisfied: {:?}", err);
+    }
+
+    let mut public = public.to_vec();
+    for n in 0..public.len() {
+        public[n] += Fp::one();
+        assert!(
+            matches!(
+                check_satisfied(k, circuit, &public),
+                Err(MockError::Unsatisfied(_))
+            ),
+            "public input {} is not constrained",
+            n
+        );
+        public[n] -= Fp::one();
+    }
+}
+
+/// Why [`cross_check`] failed.
+#[cfg(not(feature = "verify-only"))]
+#[derive(Debug)]
+pub enum CrossCheckError {
+    /// The [`MockProver`] and the real prover disagree on whether the circuit
+    /// is satisfied.
+    Mismatch {
+        mock_passed: bool,
+        prover_passed: bool,
+    },
+    /// The keys of the circuit can't be generated, e.g. because `k` is too
+    /// small.
+    Keygen(Error),
+}
+
+/// Checks `circuit` against `public` with both the [`MockProver`] and a real
+/// proof, failing if only one of them accepts it. A mismatch means a bug in
+/// the circuit or in halo2, since a proof could be rejected (or worse,
+/// accepted) where the mock says otherwise.
+#[cfg(not(feature = "verify-only"))]
+pub fn cross_check<const LVL: usize>(
+    k: u32,
+    circuit: FranchiseCircuit<LVL>,
+    public: &[Fp],
+) -> Result<(), CrossCheckError> {
+    let mock_passed = match MockProver::run(k, &circuit, vec![public.to_vec()]) {
+        Ok(prover) => prover.verify().is_ok(),
+        Err(_) => false,
+    };
+
+    let params: Params<EqAffine> = Params::new(k);
+    let empty_circuit = circuit.without_witnesses();
+    let vk = keygen_vk(&params, &empty_circuit).map_err(CrossCheckError::Keygen)?;
+    let pk = keygen_pk(&params, vk, &empty_circuit).map_err(CrossCheckError::Keygen)?;
+
+    let prover_passed = match circuit.prove(&params, &pk, public) {
+        Ok(proof) => {
+            FranchiseCircuit::<LVL>::verify(&params, pk.get_vk(), public, &proof).unwrap_or(false)
+        }
+        Err(_) => false,
+    };
+
+    if mock_passed == prover_passed {
+        Ok(())
+    } else {
+        Err(CrossCheckError::Mismatch {
+            mock_passed,
+            prover_passed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::{generate_test_data, recommended_k};
+
+    #[test]
+    fn test_check_satisfied() {
+        let (circuit, public) = generate_test_data::<3>();
+        let mut public = public.to_vec();
+        assert!(check_satisfied(recommended_k::<3>(), &circuit, &public).is_ok());
+
+        public[1] += Fp::one();
+        match check_satisfied(recommended_k::<3>(), &circuit, &public) {
+            Err(MockError::Unsatisfied(failures)) => assert!(!failures.is_empty()),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        assert!(matches!(
+            check_satisfied(2, &circuit, &public),
+            Err(MockError::Run(_))
+        ));
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn test_cross_check() {
+        let (circuit, public) = generate_test_data::<3>();
+        let public = public.to_vec();
+        assert!(cross_check(recommended_k::<3>(), circuit.clone(), &public).is_ok());
+
+        let mut broken = circuit.clone();
+        broken.pri_siblings = broken.pri_siblings.map(|mut siblings| {
+            siblings[1] += Fp::one();
+            siblings
+        });
+        assert!(cross_check(recommended_k::<3>(), broken, &public).is_ok());
+
+        assert!(matches!(
+            cross_check(2, circuit, &public),
+            Err(CrossCheckError::Keygen(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use std::convert::TryInto;
+
+use crate::proof::Proof;
+
+const LENGTH_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 32;
+const HEADER_SIZE: usize = LENGTH_SIZE + CHECKSUM_SIZE;
+
+/// Errors detected when opening a sealed [`SealedProof`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityError {
+    /// The data is too short to contain the header.
+    Truncated,
+    /// The proof length doesn't match the length in the header.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The proof doesn't match the checksum in the header.
+    ChecksumMismatch,
+}
+
+/// A [`Proof`], sealed with its length and a Blake2b checksum so that a
+/// truncated or corrupted proof is detected before attempting to verify it.
+/// The proof is sealed with [`Proof::seal`], and [`SealedProof::open`] returns
+/// it back to be verified.
+///
+/// The sealed layout is `length (u64 LE) || blake2b-256(proof) || proof`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealedProof(Vec<u8>);
+
+impl SealedProof {
+    /// Seals the proof bytes.
+    pub fn seal(proof: &Proof) -> Self {
+        let proof = proof.as_bytes();
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + proof.len());
+        bytes.extend_from_slice(&(proof.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&checksum(proof));
+        bytes.extend_from_slice(proof);
+        SealedProof(bytes)
+    }
+
+    /// Wraps sealed bytes, e.g. received from the network.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        SealedProof(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Checks the integrity of the sealed bytes and returns the proof.
+    pub fn open(&self) -> Result<Proof, IntegrityError> {
+        if self.0.len() < HEADER_SIZE {
+            return Err(IntegrityError::Truncated);
+        }
+        let (header, proof) = self.0.split_at(HEADER_SIZE);
+
+        let expected = u64::from_le_bytes(header[..LENGTH_SIZE].try_into().unwrap()) as usize;
+        if proof.len() != expected {
+            return Err(IntegrityError::LengthMismatch {
+                expected,
+                actual: proof.len(),
+            });
+        }
+
+        if header[LENGTH_SIZE..] != checksum(proof) {
+            return Err(IntegrityError::ChecksumMismatch);
+        }
+
+        Ok(Proof::from_bytes(proof.to_vec()))
+    }
+}
+
+fn checksum(proof: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(CHECKSUM_SIZE)
+        .personal(b"FranchiseProof__")
+        .hash(proof);
+
+    let mut bytes = [0u8; CHECKSUM_SIZE];
+    bytes.copy_from_slice(hash.as_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let proof = Proof::from_bytes((0..200).map(|n| n as u8).collect());
+        let sealed = proof.seal();
+        assert_eq!(sealed.open(), Ok(proof.clone()));
+
+        let mut corrupted = sealed.as_bytes().to_vec();
+        corrupted[HEADER_SIZE + 10] ^= 1;
+        assert_eq!(
+            SealedProof::from_bytes(corrupted).open(),
+            Err(IntegrityError::ChecksumMismatch)
+        );
+
+        let truncated = sealed.as_bytes()[..sealed.as_bytes().len() - 1].to_vec();
+        assert_eq!(
+            SealedProof::from_bytes(truncated).open(),
+            Err(IntegrityError::LengthMismatch {
+                expected: 200,
+                actual: 199
+            })
+        );
+
+        assert_eq!(
+            SealedProof::from_bytes(vec![0; 10]).open(),
+            Err(IntegrityError::Truncated)
+        );
+    }
+}
@@ -1,183 +1,911 @@
 #![allow(dead_code)]
 
-use crate::halo2::pasta::Fp;
+use crate::halo2::{
+    dev::MockProver,
+    pasta::{EqAffine, Fp},
+    poly::commitment::Params,
+};
+use ff::{Field, PrimeField};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+#[cfg(not(feature = "verify-only"))]
+use std::time::Instant;
+use subtle::{Choice, ConstantTimeEq};
+
+#[cfg(not(feature = "verify-only"))]
+use crate::halo2::plonk::{keygen_pk, keygen_vk};
 
 use crate::{
     franchise::FranchiseCircuit,
-    primitives::poseidon::{self, ConstantLength, P128Pow5T3},
+    merkle::{import_witness, ImportMode, MerkleError, MerkleTree, MerkleTreeBuilder},
+    primitives::poseidon::{self, ConstantLength, P128Pow5T3, VariableLength},
+    public_inputs::PublicInputs,
 };
 
-pub struct MerkleTreeBuilder {
-    depth: u32,
-    nodes: Vec<Fp>,
+/// Builds the circuit to prove the `witness` of the voter's public key, and
+/// returns it with the nullifier. The witness is imported with
+/// [`ImportMode::Truncate`], see [`generate_circuit_inputs_with_mode`] for the
+/// other modes.
+pub fn generate_circuit_inputs<const LVL: usize>(
+    secret_key: Fp,
+    process_id: [Fp; 2],
+    vote_hash: Fp,
+    witness: &[(Fp, bool)],
+) -> (FranchiseCircuit<LVL>, Fp) {
+    generate_circuit_inputs_with_mode(
+        secret_key,
+        process_id,
+        vote_hash,
+        witness,
+        ImportMode::Truncate,
+    )
+    .expect("truncating never fails")
 }
 
-pub struct MerkleTree {
-    depth: u32,
-    nodes: Vec<Fp>,
+/// Like [`generate_circuit_inputs`], importing the witness with `mode`.
+pub fn generate_circuit_inputs_with_mode<const LVL: usize>(
+    secret_key: Fp,
+    process_id: [Fp; 2],
+    vote_hash: Fp,
+    witness: &[(Fp, bool)],
+    mode: ImportMode,
+) -> Result<(FranchiseCircuit<LVL>, Fp), MerkleError> {
+    let pub_nullifier = nullifier(secret_key, process_id);
+    let (pri_index, pri_siblings) = import_witness::<LVL>(witness, mode)?;
+
+    let circuit = FranchiseCircuit {
+        pri_index: Some(pri_index),
+        pri_siblings: Some(pri_siblings),
+        pri_secret_key: Some(secret_key),
+        pub_processid: Some(process_id),
+        pub_votehash: Some(vote_hash),
+        expose_commitment: false,
+        pub_message_hash: None,
+        expose_message_hash: false,
+        leaf_prefix: None,
+        pri_claimed_nullifier: None,
+        check_claimed_nullifier: false,
+        pri_nullifier_salt: None,
+        expose_nullifier_commitment: false,
+        expose_link_tag: false,
+        expose_public_key: false,
+        pri_weight: None,
+        weighted: false,
+        pub_domain: None,
+    };
+
+    Ok((circuit, pub_nullifier))
 }
 
-impl MerkleTreeBuilder {
-    pub fn new(depth: u32) -> Self {
-        let size = 2usize.pow(depth - 1);
-        Self {
-            depth,
-            nodes: Vec::with_capacity(2 * size - 1),
-        }
+/// Checks that the `witness` of the public key of `secret_key` has `LVL` levels
+/// and leads to `root`, so that a circuit built from them can be proven. The
+/// leaf is the public key itself, as in [`generate_circuit_inputs`].
+pub fn validate_inputs<const LVL: usize>(
+    secret_key: Fp,
+    witness: &[(Fp, bool)],
+    root: Fp,
+) -> Result<(), MerkleError> {
+    if witness.len() != LVL {
+        return Err(MerkleError::InvalidLength {
+            expected: LVL,
+            actual: witness.len(),
+        });
     }
-    pub fn insert(&mut self, value: Fp) -> usize {
-        assert!(self.nodes.len() < 2usize.pow(self.depth - 1));
-        self.nodes.push(value);
-        self.nodes.len() - 1
+    let actual = MerkleTree::compute_root(secret_to_public_key(secret_key), witness);
+    if actual != root {
+        return Err(MerkleError::RootMismatch {
+            expected: root,
+            actual,
+        });
     }
+    Ok(())
+}
 
-    fn hash(first: Fp, second: Fp) -> Fp {
-        poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([first, second])
+/// Like [`generate_circuit_inputs`], failing with the error of
+/// [`validate_inputs`] instead of building a circuit that can't be proven
+/// against `root`.
+pub fn generate_circuit_inputs_checked<const LVL: usize>(
+    secret_key: Fp,
+    process_id: [Fp; 2],
+    vote_hash: Fp,
+    witness: &[(Fp, bool)],
+    root: Fp,
+) -> Result<(FranchiseCircuit<LVL>, Fp), MerkleError> {
+    validate_inputs::<LVL>(secret_key, witness, root)?;
+    generate_circuit_inputs_with_mode(
+        secret_key,
+        process_id,
+        vote_hash,
+        witness,
+        ImportMode::Strict,
+    )
+}
+
+/// Like [`generate_circuit_inputs`], taking the witness of the leaf `index` of
+/// `tree`. Fails early if that leaf is not the public key of `secret_key`, e.g.
+/// for an empty census, instead of building a circuit that can't be proven.
+pub fn generate_circuit_inputs_from_tree<const LVL: usize>(
+    tree: &MerkleTree,
+    index: usize,
+    secret_key: Fp,
+    process_id: [Fp; 2],
+    vote_hash: Fp,
+) -> Result<(FranchiseCircuit<LVL>, Fp), MerkleError> {
+    let witness = tree.witness(index)?;
+    if tree.get(index)? != secret_to_public_key(secret_key) {
+        return Err(MerkleError::NotMember { index });
     }
 
-    pub fn build(self) -> MerkleTree {
-        let MerkleTreeBuilder { depth, mut nodes } = self;
+    Ok(generate_circuit_inputs::<LVL>(
+        secret_key, process_id, vote_hash, &witness,
+    ))
+}
 
-        // fill with zeroes the unused leafs
-        let size = 2usize.pow(self.depth - 1);
-        if nodes.len() < size {
-            nodes.resize(size, Fp::zero());
-        }
+pub fn generate_test_data<const LVL: usize>() -> (FranchiseCircuit<LVL>, PublicInputs) {
+    let secret_key = Fp::from(8);
+    let process_id = [Fp::from(6), Fp::from(7)];
+    let vote_hash = Fp::from(1);
+    let public_key = secret_to_public_key(secret_key);
 
-        // compute the merkle tree nodes
-        let mut i = 0;
-        while i < nodes.capacity() - 1 {
-            nodes.push(Self::hash(nodes[i], nodes[i + 1]));
-            i += 2;
-        }
+    let witness: Vec<(Fp, bool)> = (0..LVL as u64).map(|n| (Fp::from(n), n % 2 == 0)).collect();
+    let root = MerkleTree::compute_root(public_key, &witness);
 
-        MerkleTree { depth, nodes }
+    let (circuit, nullifier) =
+        generate_circuit_inputs::<LVL>(secret_key, process_id, vote_hash, &witness);
+
+    let public = PublicInputs {
+        census_root: root,
+        nullifier,
+        vote_hash,
+    };
+
+    (circuit, public)
+}
+
+/// Like [`generate_test_data`], setting `expose_public_key` on the circuit as
+/// given and returning the public inputs it exposes, with the voter's public
+/// key appended when set.
+pub fn generate_test_data_with_public_key<const LVL: usize>(
+    expose_public_key: bool,
+) -> (FranchiseCircuit<LVL>, Vec<Fp>) {
+    let (mut circuit, public) = generate_test_data::<LVL>();
+    circuit.expose_public_key = expose_public_key;
+
+    let mut public = public.to_vec();
+    if expose_public_key {
+        let secret_key = circuit
+            .pri_secret_key
+            .expect("the test data has a secret key");
+        public.push(secret_to_public_key(secret_key));
     }
+
+    (circuit, public)
+}
+
+/// Hashes two field elements with the Poseidon instance used across the census
+/// and the circuit.
+pub fn poseidon_hash2(first: Fp, second: Fp) -> Fp {
+    poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([first, second])
 }
 
-impl MerkleTree {
-    pub fn print_tree(&self) {
-        let mut pos = (self.nodes.len() - 1) as isize;
-        let mut lvl = 1;
-        while pos >= 0 {
-            for l in 0..lvl {
-                let s = format!("{:?}", self.nodes[(pos + l) as usize]);
-                print!("{} ", &s[60..66]);
-            }
-            println!();
-            pos -= lvl * 2;
-            lvl *= 2;
+/// Hashes three field elements with the Poseidon instance of [`poseidon_hash2`]
+/// over `ConstantLength<3>`, as the circuit does natively.
+pub fn poseidon_hash3(first: Fp, second: Fp, third: Fp) -> Fp {
+    poseidon::Hash::init(P128Pow5T3, ConstantLength::<3>).hash([first, second, third])
+}
+
+/// Hashes any number of field elements with the Poseidon instance of
+/// [`poseidon_hash2`], e.g. a vote payload into its `vote_hash`. The sponge is
+/// used in its variable-length domain: capacity element `2^128 + 2^64`, and the
+/// inputs followed by a `1` and zeros up to a multiple of the rate (2) are
+/// absorbed two at a time. The result differs from [`poseidon_hash2`] for the
+/// same two inputs.
+pub fn poseidon_hash_many(inputs: &[Fp]) -> Fp {
+    poseidon::Hash::init(P128Pow5T3, VariableLength).hash(inputs)
+}
+
+/// Returns the number of voters a `FranchiseCircuit<LVL>` can prove membership
+/// for, `2^lvl`, saturating at `u128::MAX`.
+///
+/// A circuit of depth `LVL` verifies paths of `LVL` siblings, so it proves
+/// against trees of `depth = LVL + 1` levels (leaves included), which hold
+/// `2^(depth-1) = 2^LVL` leaves, one per voter.
+pub fn max_voters_for_lvl(lvl: usize) -> u128 {
+    u32::try_from(lvl)
+        .ok()
+        .and_then(|lvl| 2u128.checked_pow(lvl))
+        .unwrap_or(u128::MAX)
+}
+
+pub fn secret_to_public_key(secret_key: Fp) -> Fp {
+    poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([Fp::one(), secret_key])
+}
+
+/// Computes the leaf `Poseidon(prefix, public_key)`, as derived in-circuit when
+/// [`FranchiseCircuit::leaf_prefix`] is set.
+pub fn prefixed_leaf(prefix: Fp, public_key: Fp) -> Fp {
+    poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([prefix, public_key])
+}
+
+/// Returns the census leaf of a voter with `weight` votes in a weighted census,
+/// `Poseidon(public_key, weight)`. See [`FranchiseCircuit::weighted`].
+pub fn weighted_leaf(public_key: Fp, weight: Fp) -> Fp {
+    poseidon_hash2(public_key, weight)
+}
+
+/// Returns the census root to use as the `root` public input of the proofs
+/// against `tree`. The root doesn't depend on the process, so one census can back
+/// the proofs of many processes: each `process_id` yields a distinct nullifier
+/// for the same voter, while the `root` public input stays the same.
+pub fn shared_census_root(tree: &MerkleTree) -> Fp {
+    tree.root()
+}
+
+/// Generates the params for circuits of `2^k` rows, calling `progress` with the
+/// approximate completion in `[0, 1]`. `Params::new` does not report its
+/// progress, so only the start (`0.0`) and the end (`1.0`) are notified.
+pub fn generate_params_with_progress(k: u32, progress: &dyn Fn(f32)) -> Params<EqAffine> {
+    progress(0.0);
+    let params = Params::new(k);
+    progress(1.0);
+    params
+}
+
+/// Hardware-dependent constants used by [`estimate_proving_time`].
+#[derive(Clone, Copy, Debug)]
+pub struct Calibration {
+    /// Proving time per row and per `k`, FFTs and multiexps being `O(n log n)`.
+    pub nanos_per_row: f64,
+    /// Proving time of each of the `LVL + 3` Poseidon hashes of the circuit.
+    pub nanos_per_hash: f64,
+}
+
+impl Calibration {
+    /// Measures the calibration constants on the current hardware, by timing
+    /// two small proofs with the same `k` and different depths.
+    #[cfg(not(feature = "verify-only"))]
+    pub fn measure() -> Self {
+        const K: u32 = 8;
+        let shallow = time_proof::<1>(K).as_nanos() as f64;
+        let deep = time_proof::<3>(K).as_nanos() as f64;
+
+        let nanos_per_hash = ((deep - shallow) / 2.0).max(0.0);
+        let nanos_per_row = (shallow - 4.0 * nanos_per_hash).max(0.0) / (K * (1 << K)) as f64;
+
+        Calibration {
+            nanos_per_row,
+            nanos_per_hash,
         }
     }
+}
 
-    pub fn root(&self) -> Fp {
-        self.nodes[self.nodes.len() - 1]
-    }
-    pub fn get(&self, index: usize) -> Fp {
-        self.nodes[index]
-    }
+#[cfg(not(feature = "verify-only"))]
+fn time_proof<const LVL: usize>(k: u32) -> Duration {
+    let params: Params<EqAffine> = Params::new(k);
+    let empty_circuit = FranchiseCircuit::<LVL>::default();
+    let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+    let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+    let (circuit, public) = generate_test_data::<LVL>();
+
+    let start = Instant::now();
+    circuit
+        .prove(&params, &pk, &public.to_vec())
+        .expect("proof generation should not fail");
+    start.elapsed()
+}
+
+/// Estimates the time to prove a circuit of depth `lvl` with `2^k` rows. This is
+/// a heuristic, `nanos_per_row * 2^k * k + nanos_per_hash * (lvl + 3)`, only
+/// meaningful with a [`Calibration`] measured on the proving hardware.
+pub fn estimate_proving_time(k: u32, lvl: usize, calibration: &Calibration) -> Duration {
+    let rows = calibration.nanos_per_row * (k as f64) * 2f64.powi(k as i32);
+    let hashes = calibration.nanos_per_hash * (lvl + 3) as f64;
+    Duration::from_secs_f64((rows + hashes) / 1e9)
+}
+
+/// Returns the smallest `k` such that a circuit of depth `LVL` in the default
+/// layout fits in `2^k` rows, blinding rows included, to be used with
+/// `Params::new` and `MockProver::run`. See [`recommended_k_for`] for circuits
+/// with other modes set.
+pub fn recommended_k<const LVL: usize>() -> u32 {
+    recommended_k_for(&FranchiseCircuit::<LVL>::default())
+}
+
+/// Like [`recommended_k`], for `circuit` with the modes it has set, e.g.
+/// `expose_commitment`. The rows are counted on its actual layout, see
+/// [`FranchiseCircuit::assigned_rows`].
+pub fn recommended_k_for<const LVL: usize>(circuit: &FranchiseCircuit<LVL>) -> u32 {
+    let summary = FranchiseCircuit::<LVL>::constraint_summary();
+    let rows = (circuit.assigned_rows() + summary.blinding_factors + 1).max(summary.minimum_rows);
+    usize::BITS - (rows - 1).leading_zeros()
+}
+
+/// Computes the nullifier of `secret_key` for the process `process_id`,
+/// `Poseidon(secret_key, Poseidon(process_id[0], process_id[1]))`.
+pub fn nullifier(secret_key: Fp, process_id: [Fp; 2]) -> Fp {
+    let process_id_hash =
+        poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([process_id[0], process_id[1]]);
+
+    poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([secret_key, process_id_hash])
+}
+
+/// Like [`nullifier`], folding the `domain` separation tag of
+/// [`FranchiseCircuit::pub_domain`] with the process id hash, if any:
+/// `Poseidon(secret_key, Poseidon(process_id_hash, domain))`.
+pub fn domain_nullifier(secret_key: Fp, process_id: [Fp; 2], domain: Option<Fp>) -> Fp {
+    let process_id_hash = poseidon_hash2(process_id[0], process_id[1]);
+    let process_id_hash = match domain {
+        Some(domain) => poseidon_hash2(process_id_hash, domain),
+        None => process_id_hash,
+    };
+    poseidon_hash2(secret_key, process_id_hash)
+}
+
+/// Checks whether `nullifier` was produced by `secret_key` for `process_id`.
+/// Nullifiers are one-way, so this is only useful to parties already holding the
+/// secret, e.g. a voter's client confirming its own nullifier.
+pub fn nullifier_matches(nullifier: Fp, secret_key: Fp, process_id: [Fp; 2]) -> bool {
+    self::nullifier(secret_key, process_id) == nullifier
+}
+
+/// Domain separator of the [`link_tag`], "link" in ASCII.
+pub const LINK_DOMAIN: u64 = 0x6c69_6e6b;
 
-    pub fn witness(&self, mut index: usize) -> Vec<(Fp, bool)> {
-        let mut base = 0;
-        let mut siblings = Vec::new();
-        for n in 0..self.depth - 1 {
-            let left_right = 1 - (index & 1);
-            siblings.push((
-                self.nodes[base + (index & 0xfffe) + left_right],
-                left_right == 1,
-            ));
-            base += 2usize.pow(self.depth - n - 1);
-            index >>= 1;
+/// Returns the tag `Poseidon(secret_key, LINK_DOMAIN)` exposed by circuits with
+/// `expose_link_tag` set. Unlike the nullifier it doesn't depend on the
+/// process, so it links all the votes of a voter.
+pub fn link_tag(secret_key: Fp) -> Fp {
+    poseidon_hash2(secret_key, Fp::from(LINK_DOMAIN))
+}
+
+/// Returns the commitment `Poseidon(nullifier, salt)` exposed in place of the
+/// nullifier by circuits with `expose_nullifier_commitment` set. The voter keeps
+/// `salt` to open the commitment later with [`open_nullifier_commitment`].
+pub fn nullifier_commitment(nullifier: Fp, salt: Fp) -> Fp {
+    poseidon_hash2(nullifier, salt)
+}
+
+/// Checks whether `commitment` opens to `nullifier` with `salt`.
+pub fn open_nullifier_commitment(commitment: Fp, nullifier: Fp, salt: Fp) -> bool {
+    nullifier_commitment(nullifier, salt) == commitment
+}
+
+/// Returns the nullifiers appearing more than once in the `[root, nullifier,
+/// vote_hash]` public inputs of a batch, i.e. the double votes, each reported
+/// once in order of first appearance.
+pub fn find_duplicate_nullifiers(publics: &[PublicInputs]) -> Vec<Fp> {
+    let mut seen = HashMap::new();
+    let mut duplicates = Vec::new();
+    for public in publics {
+        let count = seen.entry(public.nullifier.to_repr()).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            duplicates.push(public.nullifier);
         }
-        siblings
     }
+    duplicates
+}
 
-    pub fn check_witness(value: Fp, siblings: Vec<(Fp, bool)>, root: Fp) -> bool {
-        let mut hash = value;
-        for (value, order) in siblings {
-            hash = if order {
-                MerkleTreeBuilder::hash(hash, value)
-            } else {
-                MerkleTreeBuilder::hash(value, hash)
-            };
-        }
-        hash == root
+/// A voter's receipt linking its nullifier to the process and the vote hash.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receipt {
+    pub nullifier: Fp,
+    /// `Poseidon(process_id[0], process_id[1])`
+    pub process_ref: Fp,
+    pub vote_hash: Fp,
+    /// `Poseidon(nullifier, vote_hash)`
+    pub receipt_hash: Fp,
+}
+
+/// Generates the receipt for the `[root, nullifier, vote_hash]` public inputs of
+/// a vote in the process `process_id`.
+pub fn generate_receipt(public: &PublicInputs, process_id: [Fp; 2]) -> Receipt {
+    Receipt {
+        nullifier: public.nullifier,
+        process_ref: poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>)
+            .hash([process_id[0], process_id[1]]),
+        vote_hash: public.vote_hash,
+        receipt_hash: poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>)
+            .hash([public.nullifier, public.vote_hash]),
     }
 }
 
-pub fn generate_circuit_inputs<const LVL: usize>(
-    secret_key: Fp,
+/// Checks that `receipt` is the receipt of the vote with the `public` inputs in
+/// the process `process_id`.
+pub fn verify_receipt(receipt: &Receipt, public: &PublicInputs, process_id: [Fp; 2]) -> bool {
+    *receipt == generate_receipt(public, process_id)
+}
+
+/// Builds the census of the voters with `secret_keys`, and for each voter the
+/// circuit and public inputs to cast `votes[n]` in the process `process_id`.
+/// Fails if there are more than `2^LVL` voters, or not one vote per voter.
+#[allow(clippy::type_complexity)]
+pub fn build_election<const LVL: usize>(
+    secret_keys: &[Fp],
     process_id: [Fp; 2],
-    vote_hash: Fp,
-    witness: &[(Fp, bool)],
-) -> (FranchiseCircuit<LVL>, Fp) {
-    let process_id_hash =
-        poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([process_id[0], process_id[1]]);
+    votes: &[Fp],
+) -> Result<(MerkleTree, Vec<(FranchiseCircuit<LVL>, PublicInputs)>), MerkleError> {
+    if secret_keys.len() != votes.len() {
+        return Err(MerkleError::VoteCountMismatch {
+            voters: secret_keys.len(),
+            votes: votes.len(),
+        });
+    }
 
-    let pub_nullifier =
-        poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([secret_key, process_id_hash]);
+    let tree = MerkleTree::from_leaf_iter(
+        LVL as u32 + 1,
+        secret_keys
+            .iter()
+            .map(|secret_key| secret_to_public_key(*secret_key)),
+    )?;
 
-    let mut pri_siblings = [Fp::zero(); LVL];
-    let mut pri_index = [false; LVL];
-    for (n, (l, p)) in witness.iter().enumerate() {
-        pri_siblings[n] = *l;
-        pri_index[n] = !p;
+    let mut voters = Vec::with_capacity(secret_keys.len());
+    for (index, (secret_key, vote_hash)) in secret_keys.iter().zip(votes.iter()).enumerate() {
+        let (circuit, nullifier) = generate_circuit_inputs_from_tree::<LVL>(
+            &tree,
+            index,
+            *secret_key,
+            process_id,
+            *vote_hash,
+        )?;
+        voters.push((
+            circuit,
+            PublicInputs {
+                census_root: tree.root(),
+                nullifier,
+                vote_hash: *vote_hash,
+            },
+        ));
     }
 
-    let circuit = FranchiseCircuit {
-        pri_index: Some(pri_index),
-        pri_siblings: Some(pri_siblings),
-        pri_secret_key: Some(secret_key),
-        pub_processid: Some(process_id),
-        pub_votehash: Some(vote_hash),
-    };
+    Ok((tree, voters))
+}
+
+/// The acceptance policy of an election, see [`ElectionConfig::validate_public`].
+/// The process id is not part of it: it is only hashed into the nullifier,
+/// which can't be checked against it without the voter's secret key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElectionConfig {
+    pub census_root: Fp,
+    pub allowed_votes: Vec<Fp>,
+}
+
+/// Reasons for [`ElectionConfig::validate_public`] and
+/// [`PublicInputs::from_vec`] to reject public inputs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// There are not exactly the `[root, nullifier, vote_hash]` public inputs.
+    InvalidLength { expected: usize, actual: usize },
+    /// The proof is against another census, or its vote hash is not one of
+    /// the allowed votes. Which one is not told, see
+    /// [`ElectionConfig::validate_public`].
+    NotAccepted,
+}
+
+impl ElectionConfig {
+    /// Checks the `[root, nullifier, vote_hash]` public inputs of a proof
+    /// against the election census and allowed votes. The nullifier can't be
+    /// checked without the voter's secret key.
+    pub fn validate_public(&self, public: &PublicInputs) -> Result<(), ValidationError> {
+        // compare in constant time and fail with a single error, so neither
+        // the time taken nor the result tells which check failed, which
+        // allowed vote or how much of the root matched
+        let root_matches = public.census_root.ct_eq(&self.census_root);
+        let vote_allowed = self
+            .allowed_votes
+            .iter()
+            .fold(Choice::from(0), |allowed, vote| {
+                allowed | public.vote_hash.ct_eq(vote)
+            });
+
+        if bool::from(root_matches & vote_allowed) {
+            Ok(())
+        } else {
+            Err(ValidationError::NotAccepted)
+        }
+    }
+}
+
+/// Builds a census of `n_members` random voters, deterministically derived from
+/// `seed`, returning the tree and the secret key of each member in leaf order.
+pub fn deterministic_census(depth: u32, n_members: usize, seed: u64) -> (MerkleTree, Vec<Fp>) {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let secret_keys: Vec<Fp> = (0..n_members).map(|_| Fp::random(&mut rng)).collect();
+
+    let mut tree = MerkleTreeBuilder::new(depth);
+    for secret_key in &secret_keys {
+        tree.insert(secret_to_public_key(*secret_key))
+            .expect("too many members for the census depth");
+    }
+
+    (tree.build(), secret_keys)
+}
+
+#[test]
+fn deterministic_census_test() {
+    let (tree, secret_keys) = deterministic_census(8, 50, 1234);
+    let (same_tree, _) = deterministic_census(8, 50, 1234);
+    assert_eq!(tree.root(), same_tree.root());
+
+    let vote_hash = Fp::from(1);
+    let (circuit, nullifier) = generate_circuit_inputs::<7>(
+        secret_keys[16],
+        [Fp::from(6), Fp::from(7)],
+        vote_hash,
+        &tree.witness(16).unwrap(),
+    );
 
-    (circuit, pub_nullifier)
+    let prover = MockProver::run(9, &circuit, vec![vec![tree.root(), nullifier, vote_hash]])
+        .expect("cannot run mock");
+    assert_eq!(Ok(()), prover.verify());
 }
 
-pub fn generate_test_data<const LVL: usize>() -> (FranchiseCircuit<LVL>, Vec<Fp>) {
+#[test]
+fn nullifier_matches_test() {
     let secret_key = Fp::from(8);
     let process_id = [Fp::from(6), Fp::from(7)];
+    let nullifier = nullifier(secret_key, process_id);
+
+    assert!(nullifier_matches(nullifier, secret_key, process_id));
+    assert!(!nullifier_matches(nullifier, Fp::from(9), process_id));
+    assert!(!nullifier_matches(
+        nullifier,
+        secret_key,
+        [Fp::from(7), Fp::from(6)]
+    ));
+    assert!(!nullifier_matches(
+        nullifier + Fp::one(),
+        secret_key,
+        process_id
+    ));
+}
+
+#[test]
+fn single_member_census_test() {
+    let secret_key = Fp::from(8);
+    let mut tree = MerkleTreeBuilder::new(2);
+    assert_eq!(tree.insert(secret_to_public_key(secret_key)), Ok(0));
+    let tree = tree.build();
+
+    let witness = tree.witness(0).unwrap();
+    assert_eq!(witness, vec![(Fp::zero(), true)]);
+    assert!(MerkleTree::check_witness(
+        tree.get(0).unwrap(),
+        witness.clone(),
+        tree.root()
+    ));
+    assert!(!MerkleTree::check_witness(
+        tree.get(1).unwrap(),
+        witness.clone(),
+        tree.root()
+    ));
+
     let vote_hash = Fp::from(1);
-    let public_key = secret_to_public_key(secret_key);
+    let (circuit, nullifier) =
+        generate_circuit_inputs::<1>(secret_key, [Fp::from(6), Fp::from(7)], vote_hash, &witness);
 
-    let mut root = public_key;
-    let mut witness = Vec::new();
-    for n in 0..LVL as u64 {
-        let direction = n % 2 == 0;
-        let value = Fp::from(n);
-        let (left, right) = if direction {
-            (root, value)
-        } else {
-            (value, root)
-        };
+    let prover = MockProver::run(8, &circuit, vec![vec![tree.root(), nullifier, vote_hash]])
+        .expect("cannot run mock");
+    assert_eq!(Ok(()), prover.verify());
+}
+
+#[test]
+fn params_progress_test() {
+    let events = std::cell::RefCell::new(Vec::new());
+    generate_params_with_progress(4, &|p| events.borrow_mut().push(p));
+    assert_eq!(*events.borrow(), vec![0.0, 1.0]);
+}
+
+#[test]
+fn shared_census_test() {
+    let (tree, secret_keys) = deterministic_census(4, 5, 42);
+    let root = shared_census_root(&tree);
+    let vote_hash = Fp::from(1);
+
+    let mut nullifiers = Vec::new();
+    for process_id in [[Fp::from(6), Fp::from(7)], [Fp::from(8), Fp::from(9)]] {
+        let (circuit, nullifier) = generate_circuit_inputs::<3>(
+            secret_keys[2],
+            process_id,
+            vote_hash,
+            &tree.witness(2).unwrap(),
+        );
 
-        let digest = poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([left, right]);
-        witness.push((value, direction));
-        root = digest;
+        let public = vec![root, nullifier, vote_hash];
+        assert_eq!(circuit.instance_values(), Some(public.clone()));
+
+        let prover =
+            MockProver::run(recommended_k::<3>(), &circuit, vec![public]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
+        nullifiers.push(nullifier);
+    }
+    assert_ne!(nullifiers[0], nullifiers[1]);
+}
+
+#[test]
+fn estimate_proving_time_test() {
+    let calibration = Calibration {
+        nanos_per_row: 1000.0,
+        nanos_per_hash: 50000.0,
+    };
+    for k in 8..12 {
+        for lvl in 1..10 {
+            let estimate = estimate_proving_time(k, lvl, &calibration);
+            assert!(estimate < estimate_proving_time(k + 1, lvl, &calibration));
+            assert!(estimate < estimate_proving_time(k, lvl + 1, &calibration));
+        }
     }
-    assert!(MerkleTree::check_witness(public_key, witness.clone(), root));
+}
+
+fn assert_recommended_k<const LVL: usize>() {
+    let (circuit, public) = generate_test_data::<LVL>();
+    assert_eq!(recommended_k::<LVL>(), recommended_k_for(&circuit));
+    assert_fits_recommended_k(&circuit, public.to_vec());
+}
+
+fn assert_fits_recommended_k<const LVL: usize>(circuit: &FranchiseCircuit<LVL>, public: Vec<Fp>) {
+    let k = recommended_k_for(circuit);
+    let satisfied = |k| match MockProver::run(k, circuit, vec![public.clone()]) {
+        Ok(prover) => prover.verify().is_ok(),
+        Err(_) => false,
+    };
+    assert!(satisfied(k), "LVL {} doesn't fit k = {}", LVL, k);
+    assert!(!satisfied(k - 1), "LVL {} fits k = {}", LVL, k - 1);
+}
+
+#[test]
+fn recommended_k_test() {
+    if cfg!(not(feature = "shared-columns")) {
+        assert_eq!(recommended_k::<3>(), 8);
+        assert_eq!(recommended_k::<9>(), 9);
+        assert_eq!(recommended_k::<21>(), 10);
+    }
+    assert_recommended_k::<3>();
+    assert_recommended_k::<4>();
+    assert_recommended_k::<9>();
+    assert_recommended_k::<10>();
+
+    // the modes adding hashes and public inputs
+    let (mut circuit, _) = generate_test_data::<3>();
+    circuit.expose_commitment = true;
+    circuit.expose_public_key = true;
+    circuit.expose_link_tag = true;
+    circuit.pub_domain = Some(Fp::from(5));
+    circuit.check_claimed_nullifier = true;
+    circuit.pri_claimed_nullifier = Some(domain_nullifier(
+        circuit.pri_secret_key.unwrap(),
+        circuit.pub_processid.unwrap(),
+        circuit.pub_domain,
+    ));
+    let public = circuit.instance_values().unwrap();
+    assert_fits_recommended_k(&circuit, public);
+}
+
+#[test]
+fn receipt_test() {
+    let (circuit, public) = generate_test_data::<3>();
+    let process_id = circuit.pub_processid.unwrap();
+
+    let receipt = generate_receipt(&public, process_id);
+    assert!(verify_receipt(&receipt, &public, process_id));
+    assert!(!verify_receipt(
+        &receipt,
+        &public,
+        [Fp::from(7), Fp::from(6)]
+    ));
+
+    let mut tampered = receipt;
+    tampered.vote_hash += Fp::one();
+    assert!(!verify_receipt(&tampered, &public, process_id));
+}
+
+#[test]
+fn duplicate_nullifiers_test() {
+    let (tree, secret_keys) = deterministic_census(4, 3, 3);
+    let process_id = [Fp::from(6), Fp::from(7)];
+    let publics: Vec<PublicInputs> = [0, 1, 2, 1]
+        .iter()
+        .map(|n| PublicInputs {
+            census_root: tree.root(),
+            nullifier: nullifier(secret_keys[*n], process_id),
+            vote_hash: Fp::from(1),
+        })
+        .collect();
+
+    assert!(find_duplicate_nullifiers(&publics[..3]).is_empty());
+    assert_eq!(
+        find_duplicate_nullifiers(&publics),
+        vec![nullifier(secret_keys[1], process_id)]
+    );
+}
+
+#[test]
+fn circuit_inputs_from_tree_test() {
+    let (tree, secret_keys) = deterministic_census(4, 3, 7);
+    let process_id = [Fp::from(6), Fp::from(7)];
 
     let (circuit, nullifier) =
-        generate_circuit_inputs::<LVL>(secret_key, process_id, vote_hash, &witness);
+        generate_circuit_inputs_from_tree::<3>(&tree, 2, secret_keys[2], process_id, Fp::one())
+            .unwrap();
+    assert_eq!(
+        circuit.instance_values(),
+        Some(vec![tree.root(), nullifier, Fp::one()])
+    );
+
+    assert!(generate_circuit_inputs_from_tree::<3>(
+        &tree,
+        1,
+        secret_keys[2],
+        process_id,
+        Fp::one()
+    )
+    .is_err());
 
-    (circuit, vec![root, nullifier, vote_hash])
+    let empty = MerkleTreeBuilder::new(4).build();
+    assert_eq!(
+        generate_circuit_inputs_from_tree::<3>(&empty, 0, secret_keys[0], process_id, Fp::one())
+            .err(),
+        Some(MerkleError::NotMember { index: 0 })
+    );
 }
 
-pub fn secret_to_public_key(secret_key: Fp) -> Fp {
-    poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash([Fp::one(), secret_key])
+#[test]
+fn max_voters_test() {
+    assert_eq!(
+        max_voters_for_lvl(21),
+        MerkleTree::max_leaves_for_depth(22).unwrap() as u128
+    );
+    assert_eq!(max_voters_for_lvl(0), 1);
+    assert_eq!(max_voters_for_lvl(128), u128::MAX);
 }
 
 #[test]
-fn simple_mt_test() {
-    let mut tree = MerkleTreeBuilder::new(6);
-    for n in 0..2u64.pow(tree.depth - 1) {
-        tree.insert(Fp::from(n));
+fn build_election_test() {
+    let secret_keys: Vec<Fp> = (1..=10u64).map(Fp::from).collect();
+    let votes: Vec<Fp> = (0..10u64).map(|n| Fp::from(n % 3)).collect();
+    let (tree, voters) =
+        build_election::<4>(&secret_keys, [Fp::from(6), Fp::from(7)], &votes).unwrap();
+    assert_eq!(voters.len(), 10);
+
+    for (circuit, public) in voters {
+        assert_eq!(public.census_root, tree.root());
+        let prover = MockProver::run(9, &circuit, vec![public.to_vec()]).expect("cannot run mock");
+        assert_eq!(Ok(()), prover.verify());
     }
-    let tree = tree.build();
-    tree.print_tree();
-    for n in 0..2usize.pow(tree.depth - 1) {
-        let witness = tree.witness(n);
-        assert!(MerkleTree::check_witness(tree.get(n), witness, tree.root()));
+
+    assert_eq!(
+        build_election::<3>(&secret_keys, [Fp::from(6), Fp::from(7)], &votes).err(),
+        Some(MerkleError::Full { capacity: 8 })
+    );
+    assert_eq!(
+        build_election::<4>(&secret_keys, [Fp::from(6), Fp::from(7)], &votes[..9]).err(),
+        Some(MerkleError::VoteCountMismatch {
+            voters: 10,
+            votes: 9
+        })
+    );
+}
+
+#[test]
+fn election_config_test() {
+    let (_, public) = generate_test_data::<3>();
+    let config = ElectionConfig {
+        census_root: public.census_root,
+        allowed_votes: vec![Fp::from(1), Fp::from(2)],
+    };
+    assert_eq!(config.validate_public(&public), Ok(()));
+
+    assert_eq!(
+        config.validate_public(&PublicInputs {
+            census_root: Fp::zero(),
+            ..public
+        }),
+        Err(ValidationError::NotAccepted)
+    );
+    assert_eq!(
+        config.validate_public(&PublicInputs {
+            vote_hash: Fp::from(3),
+            ..public
+        }),
+        Err(ValidationError::NotAccepted)
+    );
+    assert_eq!(
+        config.validate_public(&PublicInputs {
+            census_root: Fp::zero(),
+            vote_hash: Fp::from(3),
+            ..public
+        }),
+        Err(ValidationError::NotAccepted)
+    );
+}
+
+#[test]
+fn validate_inputs_test() {
+    let (tree, secret_keys) = deterministic_census(4, 5, 3);
+    let witness = tree.witness(2).unwrap();
+    let process_id = [Fp::from(6), Fp::from(7)];
+
+    assert_eq!(
+        validate_inputs::<3>(secret_keys[2], &witness, tree.root()),
+        Ok(())
+    );
+    let (circuit, nullifier) = generate_circuit_inputs_checked::<3>(
+        secret_keys[2],
+        process_id,
+        Fp::from(1),
+        &witness,
+        tree.root(),
+    )
+    .unwrap();
+    assert_eq!(
+        circuit.instance_values(),
+        Some(vec![tree.root(), nullifier, Fp::from(1)])
+    );
+
+    assert_eq!(
+        validate_inputs::<4>(secret_keys[2], &witness, tree.root()),
+        Err(MerkleError::InvalidLength {
+            expected: 4,
+            actual: 3
+        })
+    );
+    assert_eq!(
+        generate_circuit_inputs_checked::<3>(
+            secret_keys[1],
+            process_id,
+            Fp::from(1),
+            &witness,
+            tree.root()
+        )
+        .err(),
+        Some(MerkleError::RootMismatch {
+            expected: tree.root(),
+            actual: MerkleTree::compute_root(secret_to_public_key(secret_keys[1]), &witness),
+        })
+    );
+}
+
+#[test]
+fn poseidon_hash_many_test() {
+    // the hashes of [1, ..., n], computed with a standalone Python sponge over
+    // the round constants and MDS of `P128Pow5T3`, whose permutation and
+    // `ConstantLength<2>` hashes match the Zcash test vectors
+    let vectors = [
+        (
+            0,
+            "0x0846bdc0d5d1ffcd0729c63c2e71a9629c17a15b73e7d2bb65454cefc321017a",
+        ),
+        (
+            1,
+            "0x2bdeffb8f4029a15601f0d70f9169ad2cd402c2219fc8323263c7c7b919a7e03",
+        ),
+        (
+            2,
+            "0x0206844e9ec2cb9741d93d102a773c566638da5faab7e70cafd346c383509736",
+        ),
+        (
+            3,
+            "0x22bd53c3b1ac22ea2cce6cc3c192340a3b56e2dfe27de3d1a94d7c87dd1bc885",
+        ),
+        (
+            5,
+            "0x249d72127404f95af392274f3d87b7405524190b4735aadf9f03c15203799b5e",
+        ),
+    ];
+    for (n, expected) in vectors.iter() {
+        let inputs: Vec<Fp> = (1..=*n).map(Fp::from).collect();
+        assert_eq!(
+            crate::encoding::fp_to_hex(poseidon_hash_many(&inputs)),
+            *expected
+        );
     }
+
+    let (a, b) = (Fp::from(1), Fp::from(2));
+    assert_ne!(poseidon_hash_many(&[a, b]), poseidon_hash2(a, b));
+    assert_ne!(
+        poseidon_hash_many(&[a, b]),
+        poseidon_hash_many(&[a, b, Fp::zero()])
+    );
 }
@@ -0,0 +1,122 @@
+use std::process::Command;
+
+#[cfg(not(feature = "verify-only"))]
+use halo2_franchise::{
+    encoding::fp_from_hex,
+    franchise::FranchiseCircuit,
+    halo2::{
+        pasta::{EqAffine, Fp},
+        plonk::keygen_vk,
+        poly::commitment::Params,
+    },
+    proof::Proof,
+    utils::{nullifier, recommended_k},
+};
+#[cfg(not(feature = "verify-only"))]
+use std::fs;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/prove.json");
+
+fn franchise(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_franchise"))
+        .args(args)
+        .output()
+        .expect("cannot run the binary")
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[test]
+fn prove() {
+    let output_path = std::env::temp_dir().join("franchise-cli-prove.json");
+    let output = franchise(&["prove", FIXTURE, output_path.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output_path).unwrap()).unwrap();
+    fs::remove_file(&output_path).unwrap();
+    assert_eq!(output["depth"], 3);
+
+    let public_input = |name: &str| fp_from_hex(output["public_inputs"][name].as_str().unwrap());
+    let public = vec![
+        public_input("census_root").unwrap(),
+        public_input("nullifier").unwrap(),
+        public_input("vote_hash").unwrap(),
+    ];
+    assert_eq!(
+        public[0],
+        fp_from_hex("0x331a1425d7987d25487928a3a125eb399052e13002802bf8d9d0150553763f99").unwrap()
+    );
+    assert_eq!(
+        public[1],
+        nullifier(Fp::from(0x3eb), [Fp::from(6), Fp::from(7)])
+    );
+    assert_eq!(public[2], Fp::from(1));
+
+    let params: Params<EqAffine> = Params::new(recommended_k::<3>());
+    let vk = keygen_vk(&params, &FranchiseCircuit::<3>::default()).unwrap();
+    let proof: Proof = output["proof"].as_str().unwrap().parse().unwrap();
+    assert_eq!(
+        FranchiseCircuit::<3>::verify(&params, &vk, &public, &proof),
+        Ok(true)
+    );
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[test]
+fn verify() {
+    let dir = std::env::temp_dir();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (params, vk) = (
+        path("franchise-cli-params.bin"),
+        path("franchise-cli-vk.bin"),
+    );
+    let (proof, public) = (
+        path("franchise-cli-proof.json"),
+        path("franchise-cli-public.json"),
+    );
+
+    assert!(franchise(&["setup", "3", &params, &vk]).status.success());
+    assert!(franchise(&["prove", FIXTURE, &proof]).status.success());
+    let mut output: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&proof).unwrap()).unwrap();
+    fs::write(&public, output["public_inputs"].to_string()).unwrap();
+
+    let output_verify = franchise(&["verify", &params, &vk, &proof, &public]);
+    assert!(output_verify.status.success(), "{:?}", output_verify);
+    assert_eq!(String::from_utf8_lossy(&output_verify.stdout), "valid\n");
+
+    // another vote hash than the proved one
+    output["public_inputs"]["vote_hash"] = "0x02".into();
+    fs::write(&public, output["public_inputs"].to_string()).unwrap();
+    let output_verify = franchise(&["verify", &params, &vk, &proof, &public]);
+    assert_eq!(output_verify.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output_verify.stdout), "invalid\n");
+
+    // the key of another depth
+    assert!(franchise(&["setup", "4", &params, &vk]).status.success());
+    let output_verify = franchise(&["verify", &params, &vk, &proof, &public]);
+    assert!(!output_verify.status.success());
+    assert!(output_verify.stdout.is_empty());
+
+    for file in [params, vk, proof, public] {
+        fs::remove_file(file).unwrap();
+    }
+}
+
+#[cfg(feature = "verify-only")]
+#[test]
+fn prove_verify_only() {
+    let output = franchise(&["prove", FIXTURE, "output.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("verify-only"));
+}
+
+#[test]
+fn prove_invalid_input() {
+    let output = franchise(&["prove", "missing.json", "output.json"]);
+    assert!(!output.status.success());
+
+    let output = franchise(&["prove"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("usage"));
+}
@@ -0,0 +1,81 @@
+//! Verifies a proof captured once at `LVL = 3`, `k = 8` against hardcoded public
+//! inputs, so that any incompatible change to the params, the key generation,
+//! the circuit or the transcript makes it fail.
+//!
+//! After an intentional protocol change, regenerate the fixture with
+//!
+//! ```text
+//! cargo test --test golden -- --ignored regenerate_golden_proof
+//! ```
+//!
+//! and commit it along with the change, noting that older proofs no longer
+//! verify.
+//!
+//! The fixture is for the default column layout, the `shared-columns` and
+//! `compact-swap` features give another circuit.
+#![cfg(not(any(feature = "shared-columns", feature = "compact-swap")))]
+
+use halo2_franchise::encoding::fp_from_hex;
+use halo2_franchise::franchise::FranchiseCircuit;
+use halo2_franchise::halo2::{
+    pasta::{EqAffine, Fp},
+    plonk::keygen_vk,
+    poly::commitment::Params,
+};
+use halo2_franchise::proof::Proof;
+
+const K: u32 = 8;
+const GOLDEN_PROOF: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/golden_proof_lvl3_k8.bin"
+);
+
+/// The `[root, nullifier, vote_hash]` of `generate_test_data::<3>()`.
+fn public_inputs() -> Vec<Fp> {
+    [
+        "0x179f2c66cc0fe3d8791282ca5d550e09928d3fa33bef40b7ebd10ca4aa893d53",
+        "0x312e736b1d71b9bf28dbfb9a23e3b7f1d55c53aea3775d752d0a226cf0f50366",
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
+    ]
+    .iter()
+    .map(|hex| fp_from_hex(hex).unwrap())
+    .collect()
+}
+
+#[test]
+fn golden_proof() {
+    let params: Params<EqAffine> = Params::new(K);
+    let vk = keygen_vk(&params, &FranchiseCircuit::<3>::default()).unwrap();
+    let proof = Proof::from_bytes(std::fs::read(GOLDEN_PROOF).unwrap());
+
+    let public = public_inputs();
+    assert_eq!(
+        FranchiseCircuit::<3>::verify(&params, &vk, &public, &proof),
+        Ok(true)
+    );
+
+    let mut other = public;
+    other[2] += Fp::one();
+    assert_eq!(
+        FranchiseCircuit::<3>::verify(&params, &vk, &other, &proof),
+        Ok(false)
+    );
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[test]
+#[ignore]
+fn regenerate_golden_proof() {
+    use halo2_franchise::halo2::plonk::keygen_pk;
+    use halo2_franchise::utils::generate_test_data;
+
+    let params: Params<EqAffine> = Params::new(K);
+    let empty_circuit = FranchiseCircuit::<3>::default();
+    let vk = keygen_vk(&params, &empty_circuit).unwrap();
+    let pk = keygen_pk(&params, vk, &empty_circuit).unwrap();
+
+    let (circuit, public) = generate_test_data::<3>();
+    assert_eq!(public.to_vec(), public_inputs());
+    let proof = circuit.prove(&params, &pk, &public.to_vec()).unwrap();
+    std::fs::write(GOLDEN_PROOF, proof.as_bytes()).unwrap();
+}